@@ -1,9 +1,11 @@
 #![feature(test)]
 extern crate test;
 
-use input_stream::InputStream;
+use input_stream::{Error, InputStream};
 use rand::distributions::{Distribution, Standard};
 use rand::Rng;
+use std::io::BufReader;
+use std::num::{IntErrorKind, ParseIntError};
 use std::str::FromStr;
 use test::Bencher;
 
@@ -31,7 +33,7 @@ where
     let mut stream = InputStream::new(input.as_bytes());
 
     let mut count = 0;
-    while let Ok(_) = stream.scan::<T>() {
+    while stream.scan::<T>().is_ok() {
         count += 1;
     }
     count
@@ -67,3 +69,129 @@ num_bench! {
     (f32, f32_bench),
     (f64, f64_bench)
 }
+
+// Whether a number was scanned rather than the end of the input, which shows up as an empty
+// token. Any other error fails the benchmark, so a bad parse can't pass for a fast run.
+fn scanned<T>(result: Result<T, Error<ParseIntError>>) -> bool {
+    match result {
+        Ok(_) => true,
+        Err(Error::FromStr(ref err)) if *err.kind() == IntErrorKind::Empty => false,
+        Err(err) => panic!("unexpected error: {:?}", err),
+    }
+}
+
+fn generate_delimited(many: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..many)
+        .map(|_| rng.gen::<u32>().to_string())
+        .collect::<Vec<_>>()
+        .join(",;")
+}
+
+#[bench]
+fn delimiter_closure_bench(b: &mut Bencher) {
+    let numbers = generate_delimited(NUMBERS_GENERATED);
+
+    b.iter(|| {
+        let mut stream = InputStream::new(numbers.as_bytes());
+        let mut count = 0;
+        while scanned(stream.scan_with_delimiter::<u32, _>(|c| c == b',' || c == b';')) {
+            count += 1;
+        }
+        assert_eq!(count, NUMBERS_GENERATED);
+    });
+}
+
+#[bench]
+fn delimiter_set_bench(b: &mut Bencher) {
+    let numbers = generate_delimited(NUMBERS_GENERATED);
+
+    b.iter(|| {
+        let mut stream = InputStream::with_delimiter_set(numbers.as_bytes(), b",;");
+        let mut count = 0;
+        while scanned(stream.scan::<u32>()) {
+            count += 1;
+        }
+        assert_eq!(count, NUMBERS_GENERATED);
+    });
+}
+
+fn generate_csv(many: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..many)
+        .map(|_| {
+            (0..20)
+                .map(|_| rng.gen_range(b'a', b'z' + 1) as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[bench]
+fn single_delimiter_closure_bench(b: &mut Bencher) {
+    let fields = generate_csv(NUMBERS_GENERATED);
+
+    b.iter(|| {
+        let mut stream = InputStream::new(fields.as_bytes());
+        let mut count = 0;
+        while !stream
+            .scan_with_delimiter::<String, _>(|c| c == b',')
+            .expect("a field")
+            .is_empty()
+        {
+            count += 1;
+        }
+        assert_eq!(count, NUMBERS_GENERATED);
+    });
+}
+
+#[bench]
+fn single_delimiter_fast_bench(b: &mut Bencher) {
+    let fields = generate_csv(NUMBERS_GENERATED);
+
+    b.iter(|| {
+        let mut stream = InputStream::new(fields.as_bytes());
+        let mut count = 0;
+        while !stream
+            .scan_delimited_fast::<String>(b',')
+            .expect("a field")
+            .is_empty()
+        {
+            count += 1;
+        }
+        assert_eq!(count, NUMBERS_GENERATED);
+    });
+}
+
+#[bench]
+fn long_tokens_bench(b: &mut Bencher) {
+    let mut rng = rand::thread_rng();
+    let words: Vec<String> = (0..NUMBERS_GENERATED / 10)
+        .map(|_| {
+            (0..100)
+                .map(|_| rng.gen_range(b'a', b'z' + 1) as char)
+                .collect()
+        })
+        .collect();
+    let text = words.join("\n");
+
+    b.iter(|| {
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(words.len(), stream.tokens().count());
+    });
+}
+
+#[bench]
+fn buffered_numbers_bench(b: &mut Bencher) {
+    let numbers = generate_numbers::<u64>(NUMBERS_GENERATED);
+
+    b.iter(|| {
+        let mut stream = InputStream::new(BufReader::new(numbers.as_bytes()));
+        let mut count = 0;
+        while scanned(stream.scan::<u64>()) {
+            count += 1;
+        }
+        assert_eq!(count, NUMBERS_GENERATED);
+    });
+}
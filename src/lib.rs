@@ -23,6 +23,19 @@
 //! extern crate input_stream;
 //! ```
 //!
+//! # Features
+//!
+//! - `anyhow`: scan straight into an [anyhow](https://crates.io/crates/anyhow) result with
+//!   [`scan_anyhow`](struct.InputStream.html#method.scan_anyhow).
+//! - `memchr`: find the end of tokens with the [memchr](https://crates.io/crates/memchr) crate,
+//!   which speeds up inputs with long tokens but is no faster for short numeric ones.
+//! - `regex`: validate tokens against a [regex](https://crates.io/crates/regex) with
+//!   [`scan_regex`](struct.InputStream.html#method.scan_regex), or split text on one with
+//!   [`scan_until_regex`](struct.InputStream.html#method.scan_until_regex).
+//! - `smallvec`: scan small groups of values without allocating, into a
+//!   [smallvec](https://crates.io/crates/smallvec), with
+//!   [`scan_smallvec`](struct.InputStream.html#method.scan_smallvec).
+//!
 //! # Examples:
 //!
 //! ```rust
@@ -85,9 +98,22 @@
     warnings
 )]
 
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::convert::{Infallible, TryInto};
 use std::fmt::{self, Debug, Display, Formatter};
-use std::io::{self, BufRead, Read};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::mem;
+use std::net::{IpAddr, SocketAddr};
+use std::num::{self, ParseIntError};
+use std::ops::{Range, RangeInclusive};
 use std::str::{self, FromStr};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
 
 /// The type of errors this library can return.
 #[derive(Debug)]
@@ -100,6 +126,41 @@ pub enum Error<E> {
     FromStr(E),
     /// Buffer limit exceeded
     BufferLimitExceeded,
+    /// A line did not contain the expected number of values
+    WrongCount {
+        /// How many values were expected
+        expected: usize,
+        /// How many values were found
+        found: usize,
+    },
+    /// A token did not have the expected shape
+    Unexpected {
+        /// The offending token
+        found: String,
+        /// A description of what was expected instead
+        expected: String,
+    },
+    /// A value that must be nonzero was zero
+    Zero,
+    /// An unsigned value was prefixed by the given sign
+    Signed(char),
+    /// A token is not valid utf8
+    Utf8Token {
+        /// The raw bytes of the token
+        token: Vec<u8>,
+        /// Where decoding failed within the token, see
+        /// [`Utf8Error::valid_up_to`](https://doc.rust-lang.org/std/str/struct.Utf8Error.html#method.valid_up_to)
+        error: str::Utf8Error,
+    },
+    /// A token contained a character that is not allowed
+    InvalidChar {
+        /// The offending character
+        found: char,
+        /// The byte offset of the character within the token
+        position: usize,
+    },
+    /// The input ended before the given fields of a record were found
+    MissingFields(Vec<String>),
 }
 
 /// A specialized [`Result`](https://doc.rust-lang.org/std/result/enum.Result.html) for this
@@ -125,199 +186,5520 @@ impl<E> Display for Error<E> {
             Error::Utf8(_) => write!(fmt, "Data is not valid utf8"),
             Error::FromStr(_) => write!(fmt, "Could not parse given data type"),
             Error::BufferLimitExceeded => write!(fmt, "Buffer limit exceeded"),
+            Error::WrongCount { expected, found } => {
+                write!(fmt, "Expected {} values, found {}", expected, found)
+            }
+            Error::Unexpected { found, expected } => {
+                write!(fmt, "Expected {}, found `{}`", expected, found)
+            }
+            Error::Zero => write!(fmt, "Value must be nonzero"),
+            Error::Signed(sign) => write!(fmt, "Unsigned value has a `{}` sign", sign),
+            Error::Utf8Token { token, error } => write!(
+                fmt,
+                "Token {:?} is not valid utf8 after byte {}",
+                String::from_utf8_lossy(token),
+                error.valid_up_to()
+            ),
+            Error::InvalidChar { found, position } => write!(
+                fmt,
+                "Character {:?} at byte {} of the token is not allowed",
+                found, position
+            ),
+            Error::MissingFields(names) => write!(fmt, "Missing fields {}", names.join(", ")),
         }
     }
 }
 
 impl<E: Debug> std::error::Error for Error<E> {}
 
+/// Turns errors that can only come from reading, not parsing, into an
+/// [`std::io::Error`](https://doc.rust-lang.org/std/io/struct.Error.html).
+fn into_io_error<E>(err: Error<E>) -> io::Error {
+    match err {
+        Error::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
 /// A wrapper for [`std::io::BufRead`](https://doc.rust-lang.org/std/io/trait.BufRead.html).
 ///
 /// To get an instance of this  struct use static method [`new`](struct.InputStream.html#method.new) on
 /// `InputStream`.
 #[derive(Debug)]
 pub struct InputStream<T: BufRead> {
-    reader: T,
+    reader: Pushback<T>,
     byte_buffer: Vec<u8>,
+    delimiters: Delimiters,
+    keep_empty_fields: bool,
+    skip_trailing_delimiter: bool,
+    /// Whether the last thing read was a token, whose delimiter is still to be skipped.
+    after_token: bool,
 }
 
-#[inline(always)]
-fn is_whitespace(c: u8) -> bool {
-    match c {
-        b' ' | b'\x09'...b'\x0d' => true,
-        _ => false,
+/// A lookup table of the bytes that separate tokens.
+#[derive(Clone)]
+struct Delimiters {
+    table: [bool; 256],
+    #[cfg(feature = "memchr")]
+    bytes: Vec<u8>,
+}
+
+impl Delimiters {
+    fn new(bytes: &[u8]) -> Delimiters {
+        let mut table = [false; 256];
+        for &c in bytes {
+            table[usize::from(c)] = true;
+        }
+        Delimiters {
+            table,
+            #[cfg(feature = "memchr")]
+            bytes: {
+                // The likelier delimiters go first, see `find`.
+                let common = b" \n\t,;";
+                let rare = (0..=255u8).filter(|c| !common.contains(c));
+                common
+                    .iter()
+                    .cloned()
+                    .chain(rare)
+                    .filter(|&c| table[usize::from(c)])
+                    .collect()
+            },
+        }
+    }
+
+    #[inline(always)]
+    fn contains(&self, c: u8) -> bool {
+        self.table[usize::from(c)]
+    }
+
+    /// Returns the position of the first delimiter in `buf`.
+    #[cfg(not(feature = "memchr"))]
+    #[inline(always)]
+    fn find(&self, buf: &[u8]) -> Option<usize> {
+        buf.iter().position(|&c| self.contains(c))
+    }
+
+    /// Returns the position of the first delimiter in `buf`, searching for up to six distinct
+    /// delimiters with `memchr`.
+    #[cfg(feature = "memchr")]
+    #[inline(always)]
+    fn find(&self, buf: &[u8]) -> Option<usize> {
+        // With more than three delimiters the first three are searched for in windows, so a
+        // missing one doesn't make every search run to the end of the buffer, and the rest only
+        // up to the first match.
+        const WINDOW: usize = 64;
+        match *self.bytes.as_slice() {
+            [] => None,
+            [a] => memchr::memchr(a, buf),
+            [a, b] => memchr::memchr2(a, b, buf),
+            [a, b, c] => memchr::memchr3(a, b, c, buf),
+            [a, b, c, ref rest @ ..] if rest.len() <= 3 => {
+                buf.chunks(WINDOW).enumerate().find_map(|(index, window)| {
+                    let first = memchr::memchr3(a, b, c, window);
+                    let window = &window[..first.unwrap_or(window.len())];
+                    let second = match *rest {
+                        [d] => memchr::memchr(d, window),
+                        [d, e] => memchr::memchr2(d, e, window),
+                        [d, e, f, ..] => memchr::memchr3(d, e, f, window),
+                        [] => None,
+                    };
+                    second.or(first).map(|position| index * WINDOW + position)
+                })
+            }
+            _ => buf.iter().position(|&c| self.contains(c)),
+        }
     }
 }
 
-#[inline(always)]
-fn act_while<T, F, G, E>(reader: &mut T, mut condition: F, mut act: G) -> Result<(), Error<E>>
-where
-    T: BufRead,
-    F: FnMut(&&u8) -> bool,
-    G: FnMut(&[u8]) -> Result<(), Error<E>>,
-{
-    loop {
-        let (skipped, done) = match reader.fill_buf() {
-            Ok(buf) => {
-                let skipped = buf.iter().take_while(&mut condition).count();
-                act(&buf[..skipped])?;
-                (skipped, skipped < buf.len() || buf.is_empty())
-            }
-            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-            Err(e) => return Err(e.into()),
+impl Default for Delimiters {
+    fn default() -> Delimiters {
+        Delimiters::new(b" \x09\x0a\x0b\x0c\x0d")
+    }
+}
+
+impl Debug for Delimiters {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_set()
+            .entries((0..=255u8).filter(|&c| self.contains(c)))
+            .finish()
+    }
+}
+
+/// A builder for an [`InputStream`](struct.InputStream.html) with several options set at once.
+///
+/// Every option starts out with the same default as for
+/// [`InputStream::new`](struct.InputStream.html#method.new), and the builder can be reused to
+/// configure any number of streams.
+#[derive(Clone, Debug)]
+pub struct InputStreamBuilder {
+    delimiters: Delimiters,
+    interrupt_retries: Option<usize>,
+    keep_empty_fields: bool,
+    skip_trailing_delimiter: bool,
+    track_lines: bool,
+    max_empty_fills: usize,
+}
+
+impl InputStreamBuilder {
+    /// Creates a builder with every option set to its default.
+    pub fn new() -> InputStreamBuilder {
+        InputStreamBuilder {
+            delimiters: Delimiters::default(),
+            interrupt_retries: None,
+            keep_empty_fields: false,
+            skip_trailing_delimiter: false,
+            track_lines: false,
+            max_empty_fills: 0,
+        }
+    }
+
+    /// Separates values by any of the given `delimiters`, like
+    /// [`with_delimiter_set`](struct.InputStream.html#method.with_delimiter_set). Defaults to
+    /// ASCII whitespace.
+    pub fn delimiters(mut self, delimiters: &[u8]) -> Self {
+        self.delimiters = Delimiters::new(delimiters);
+        self
+    }
+
+    /// Sets how many interrupted reads in a row are retried, like
+    /// [`set_interrupt_retries`](struct.InputStream.html#method.set_interrupt_retries).
+    /// Defaults to `None`, retrying forever.
+    pub fn interrupt_retries(mut self, max: Option<usize>) -> Self {
+        self.interrupt_retries = max;
+        self
+    }
+
+    /// Sets whether consecutive delimiters enclose empty values, like
+    /// [`keep_empty_fields`](struct.InputStream.html#method.keep_empty_fields). Defaults to
+    /// `false`.
+    pub fn keep_empty_fields(mut self, keep: bool) -> Self {
+        self.keep_empty_fields = keep;
+        self
+    }
+
+    /// Sets whether a delimiter right before the end of a line is skipped, like
+    /// [`skip_trailing_delimiter`](struct.InputStream.html#method.skip_trailing_delimiter).
+    /// Defaults to `false`.
+    pub fn skip_trailing_delimiter(mut self, skip: bool) -> Self {
+        self.skip_trailing_delimiter = skip;
+        self
+    }
+
+    /// Sets whether consumed lines are counted, like
+    /// [`track_lines`](struct.InputStream.html#method.track_lines). Defaults to `false`.
+    pub fn track_lines(mut self, track: bool) -> Self {
+        self.track_lines = track;
+        self
+    }
+
+    /// Sets after how many empty reads in a row the stream gives up with an error, like
+    /// [`set_max_empty_fills`](struct.InputStream.html#method.set_max_empty_fills). Defaults to
+    /// `0`, where an empty read is the end of the input.
+    pub fn max_empty_fills(mut self, n: usize) -> Self {
+        self.max_empty_fills = n;
+        self
+    }
+
+    /// Creates an [`InputStream`](struct.InputStream.html) with these options, which wraps the
+    /// given [`std::io::BufRead`](https://doc.rust-lang.org/std/io/trait.BufRead.html).
+    pub fn build<T: BufRead>(&self, reader: T) -> InputStream<T> {
+        let mut stream = InputStream {
+            delimiters: self.delimiters.clone(),
+            keep_empty_fields: self.keep_empty_fields,
+            skip_trailing_delimiter: self.skip_trailing_delimiter,
+            ..InputStream::new(reader)
         };
+        stream.set_interrupt_retries(self.interrupt_retries);
+        stream.track_lines(self.track_lines);
+        stream.set_max_empty_fills(self.max_empty_fills);
+        stream
+    }
+}
 
-        reader.consume(skipped);
-        if done {
-            break;
+impl Default for InputStreamBuilder {
+    fn default() -> InputStreamBuilder {
+        InputStreamBuilder::new()
+    }
+}
+
+/// Types that know how to scan themselves out of an [`InputStream`](struct.InputStream.html),
+/// possibly as several values.
+///
+/// Every `Vec<F>` where `F` implements
+/// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html) is `Scannable`,
+/// by scanning values until the end of the input.
+pub trait Scannable: Sized {
+    /// The error returned when parsing one of the values fails.
+    type Err;
+
+    /// Scans a value out of `stream`.
+    fn scan_from<T: BufRead>(stream: &mut InputStream<T>) -> Result<Self, Error<Self::Err>>;
+}
+
+impl<F: FromStr> Scannable for Vec<F> {
+    type Err = F::Err;
+
+    fn scan_from<T: BufRead>(stream: &mut InputStream<T>) -> Result<Self, Error<Self::Err>> {
+        let mut values = Vec::new();
+        loop {
+            if !stream.next_token()? {
+                return Ok(values);
+            }
+            values.push(stream.parse_token()?);
         }
     }
-    Ok(())
 }
 
-impl<T: BufRead> InputStream<T> {
-    /// Creates an instance of InputStream which wraps the given
-    /// [`std::io::BufRead`](https://doc.rust-lang.org/std/io/trait.BufRead.html).
-    #[inline(always)]
-    pub fn new(reader: T) -> InputStream<T> {
-        InputStream {
-            reader,
-            byte_buffer: Vec::new(),
+/// The nonzero integer types of
+/// [`std::num`](https://doc.rust-lang.org/std/num/index.html), which can be scanned with
+/// [`scan_nonzero`](struct.InputStream.html#method.scan_nonzero).
+pub trait NonZeroInteger: Sized {
+    /// The primitive integer type wrapped by this type.
+    type Primitive: FromStr<Err = ParseIntError>;
+
+    /// Wraps `value`, or returns `None` if it is zero.
+    fn new(value: Self::Primitive) -> Option<Self>;
+}
+
+macro_rules! non_zero_integer {
+    ($(($non_zero: ty, $primitive: ty)),*) => {
+        $(
+            impl NonZeroInteger for $non_zero {
+                type Primitive = $primitive;
+
+                #[inline(always)]
+                fn new(value: $primitive) -> Option<Self> {
+                    <$non_zero>::new(value)
+                }
+            }
+        )*
+    }
+}
+
+non_zero_integer! {
+    (num::NonZeroU8, u8),
+    (num::NonZeroU16, u16),
+    (num::NonZeroU32, u32),
+    (num::NonZeroU64, u64),
+    (num::NonZeroU128, u128),
+    (num::NonZeroUsize, usize),
+    (num::NonZeroI8, i8),
+    (num::NonZeroI16, i16),
+    (num::NonZeroI32, i32),
+    (num::NonZeroI64, i64),
+    (num::NonZeroI128, i128),
+    (num::NonZeroIsize, isize)
+}
+
+/// The primitive integer types, which can be scanned with a radix prefix with
+/// [`scan_radix_int`](struct.InputStream.html#method.scan_radix_int).
+pub trait RadixInteger: FromStr<Err = ParseIntError> {
+    /// Parses `src` in the given `radix`, like the inherent `from_str_radix` of the type.
+    fn from_str_radix(src: &str, radix: u32) -> Result<Self, ParseIntError>;
+}
+
+macro_rules! radix_integer {
+    ($($primitive: ty),*) => {
+        $(
+            impl RadixInteger for $primitive {
+                #[inline(always)]
+                fn from_str_radix(src: &str, radix: u32) -> Result<Self, ParseIntError> {
+                    <$primitive>::from_str_radix(src, radix)
+                }
+            }
+        )*
+    }
+}
+
+radix_integer!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// The unsigned integer types, which can be scanned with
+/// [`scan_uint`](struct.InputStream.html#method.scan_uint).
+pub trait Unsigned: FromStr {}
+
+impl Unsigned for u8 {}
+impl Unsigned for u16 {}
+impl Unsigned for u32 {}
+impl Unsigned for u64 {}
+impl Unsigned for u128 {}
+impl Unsigned for usize {}
+
+/// An iterator over the characters of an [`InputStream`](struct.InputStream.html).
+///
+/// This struct is created by the [`chars`](struct.InputStream.html#method.chars) method on
+/// `InputStream`.
+#[derive(Debug)]
+pub struct CharsIter<'a, T: BufRead> {
+    stream: &'a mut InputStream<T>,
+    skip_whitespace: bool,
+}
+
+impl<'a, T: BufRead> CharsIter<'a, T> {
+    /// Makes the iterator skip whitespace characters instead of yielding them.
+    pub fn skip_whitespace(mut self) -> Self {
+        self.skip_whitespace = true;
+        self
+    }
+}
+
+impl<'a, T: BufRead> Iterator for CharsIter<'a, T> {
+    type Item = Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = &mut self.stream.reader;
+        if self.skip_whitespace {
+            if let Err(err) = act_while(reader, |&&c| is_whitespace(c), |_| Ok(())) {
+                return Some(Err(err));
+            }
         }
+        read_char(reader).transpose()
     }
+}
 
-    /// Scan the underlying buffered reader for a value of a type that implements
-    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html)
-    /// returning a [`Result`](type.Result.html).
-    ///
-    /// An example on how to use scan is at the [`crate documentation`](index.html).
-    pub fn scan<F: FromStr>(&mut self) -> Result<F> {
-        self.inner_scan(None)
+/// An iterator over the values of an [`InputStream`](struct.InputStream.html), scanned until the
+/// end of the input.
+///
+/// This struct is created by the [`scan_iter`](struct.InputStream.html#method.scan_iter) method
+/// on `InputStream`.
+#[derive(Debug)]
+pub struct ScanIter<'a, T: BufRead, F> {
+    stream: &'a mut InputStream<T>,
+    marker: PhantomData<fn() -> F>,
+}
+
+impl<'a, T: BufRead, F: FromStr> Iterator for ScanIter<'a, T, F> {
+    type Item = Result<F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stream.next_token() {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => return Some(Err(err)),
+        }
+        Some(self.stream.parse_token())
     }
+}
 
-    /// Scan the underlying buffer reader for a value of a type that implements
-    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html)
-    /// returning a [`Result`](type.Result.html).
-    ///
-    /// This is a refined version of [`scan`](struct.InputStream.html#method.scan) which allows
-    /// limits to be placed on the maximum size of the internal buffer
-    pub fn scan_with_limit<F: FromStr>(&mut self, limit: usize) -> Result<F> {
-        self.inner_scan(Some(limit))
+/// An iterator over the lines of an [`InputStream`](struct.InputStream.html), without their
+/// terminators.
+///
+/// This struct is created by the [`lines`](struct.InputStream.html#method.lines) method on
+/// `InputStream`.
+#[derive(Debug)]
+pub struct LinesIter<'a, T: BufRead> {
+    stream: &'a mut InputStream<T>,
+}
+
+impl<'a, T: BufRead> Iterator for LinesIter<'a, T> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stream.read_line(None) {
+            Ok(true) => Some(
+                str::from_utf8(&self.stream.byte_buffer)
+                    .map(str::to_string)
+                    .map_err(Error::from),
+            ),
+            Ok(false) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+type TokenSplit<'a> = str::Split<'a, fn(char) -> bool>;
+
+/// An iterator over the whitespace separated tokens of a line, borrowed from the
+/// [`InputStream`](struct.InputStream.html) that read it.
+///
+/// This struct is created by the [`line_tokens`](struct.InputStream.html#method.line_tokens)
+/// method on `InputStream`.
+#[derive(Clone, Debug)]
+pub struct LineTokens<'a> {
+    tokens: std::iter::Filter<TokenSplit<'a>, fn(&&'a str) -> bool>,
+}
+
+impl<'a> Iterator for LineTokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.next()
     }
+}
+
+/// Wraps the reader with a buffer of bytes that were handed back to the stream, which are served
+/// before anything else from the reader.
+#[derive(Debug)]
+struct Pushback<T> {
+    reader: T,
+    buffer: Vec<u8>,
+    position: usize,
+    interrupt_retries: Option<usize>,
+    /// How many empty fills in a row make the reader stalled, if not `0`, in which case an empty
+    /// fill is the end of the input.
+    max_empty_fills: usize,
+    consumed: u64,
+    /// Whether `newlines` is kept up to date, which costs a pass over every consumed byte.
+    track_lines: bool,
+    newlines: u64,
+    /// How far below zero the counts of consumed bytes and newlines are, after bytes that were
+    /// never read, such as a char pushed back at the start, were handed back. These are paid
+    /// off before anything more is counted.
+    owed: u64,
+    owed_newlines: u64,
+    /// The consumed and owed byte counts from before the char handed back by `push_back_char`,
+    /// which are reached again once it has been read.
+    pushed_back: Option<(u64, u64)>,
+    /// Bytes already counted as consumed but still in the window, because a token borrowed
+    /// from it was handed out. They are dropped on the next access.
+    deferred: usize,
+}
 
+impl<T: BufRead> Pushback<T> {
     #[inline(always)]
-    fn inner_scan<F: FromStr>(&mut self, limit: Option<usize>) -> Result<F> {
-        let &mut InputStream {
-            ref mut reader,
-            ref mut byte_buffer,
-        } = self;
-        act_while(reader, |&&c| is_whitespace(c), |_| Ok(()))?;
-        byte_buffer.clear();
-        act_while(
+    fn new(reader: T) -> Pushback<T> {
+        Pushback {
             reader,
-            |&&c| !is_whitespace(c),
-            |slice| {
-                if let Some(limit) = limit {
-                    if byte_buffer.len() + slice.len() > limit {
-                        return Err(Error::BufferLimitExceeded);
+            buffer: Vec::new(),
+            position: 0,
+            interrupt_retries: None,
+            max_empty_fills: 0,
+            consumed: 0,
+            track_lines: false,
+            newlines: 0,
+            owed: 0,
+            owed_newlines: 0,
+            pushed_back: None,
+            deferred: 0,
+        }
+    }
+
+    /// Like `fill_buf`, but retries reads that were interrupted, giving up after
+    /// `interrupt_retries` retries if it is set, and reads that came back empty if
+    /// `max_empty_fills` is set, failing once that many came back empty in a row.
+    #[inline(always)]
+    fn fill_buf_retrying(&mut self) -> io::Result<&[u8]> {
+        self.settle();
+        let max_retries = self.interrupt_retries;
+        let max_empty_fills = self.max_empty_fills;
+        let mut retries = 0;
+        let mut empty_fills = 0;
+        loop {
+            match self.window() {
+                Ok(buf) if buf.is_empty() && max_empty_fills > 0 => {
+                    empty_fills += 1;
+                    if empty_fills == max_empty_fills {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("the reader came back empty {} times in a row", empty_fills),
+                        ));
                     }
                 }
+                // Filling again would read past the end of the input.
+                Ok([]) => return Ok(&[]),
+                Ok(_) => break,
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::Interrupted
+                        && max_retries.is_none_or(|max| retries < max) =>
+                {
+                    retries += 1
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.window()
+    }
 
-                byte_buffer.extend_from_slice(slice);
-                Ok(())
-            },
-        )?;
-
-        let slice = match byte_buffer.split_last() {
-            Some((&b' ', slice)) => slice,
-            _ => byte_buffer.as_slice(),
-        };
-
-        str::from_utf8(slice)?.parse().map_err(Error::FromStr)
+    /// Counts `amount` bytes of the current window as consumed, but keeps them in the window
+    /// until the next access, so that a slice of them can still be borrowed.
+    #[inline(always)]
+    fn consume_deferred(&mut self, amount: usize) {
+        self.settle();
+        let newlines = self.count_lines(amount);
+        self.count(amount as u64, newlines);
+        self.deferred = amount;
     }
-}
 
-impl<T: BufRead> Read for InputStream<T> {
+    /// Counts the newlines among the first `amount` bytes of the current window, if lines are
+    /// tracked, and `0` otherwise.
     #[inline(always)]
-    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
-        self.reader.read(buffer)
+    fn count_lines(&mut self, amount: usize) -> u64 {
+        if !self.track_lines {
+            return 0;
+        }
+        // The window is already filled, so this does not read anything.
+        match self.window() {
+            Ok(window) => count_newlines(&window[..amount.min(window.len())]),
+            Err(_) => 0,
+        }
     }
-}
 
-impl<T: BufRead> BufRead for InputStream<T> {
+    /// Counts `bytes` bytes holding `newlines` newlines as consumed, paying off what is owed
+    /// first.
     #[inline(always)]
-    fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        self.reader.fill_buf()
+    fn count(&mut self, bytes: u64, newlines: u64) {
+        if self.owed == 0 && self.owed_newlines == 0 {
+            self.consumed += bytes;
+            self.newlines += newlines;
+        } else {
+            self.count_owed(bytes, newlines);
+        }
+    }
+
+    #[cold]
+    fn count_owed(&mut self, bytes: u64, newlines: u64) {
+        let paid = bytes.min(self.owed);
+        self.owed -= paid;
+        self.consumed += bytes - paid;
+        let paid = newlines.min(self.owed_newlines);
+        self.owed_newlines -= paid;
+        self.newlines += newlines - paid;
     }
 
+    /// Takes `bytes` bytes holding `newlines` newlines off the consumed counts, owing whatever
+    /// would take them below zero.
+    fn uncount(&mut self, bytes: u64, newlines: u64) {
+        let taken = bytes.min(self.consumed);
+        self.consumed -= taken;
+        self.owed += bytes - taken;
+        let taken = newlines.min(self.newlines);
+        self.newlines -= taken;
+        self.owed_newlines += newlines - taken;
+    }
+
+    /// Returns the current window, including any bytes whose consumption was deferred.
     #[inline(always)]
-    fn consume(&mut self, amount: usize) {
-        self.reader.consume(amount)
+    fn window(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.buffer.len() {
+            self.reader.fill_buf()
+        } else {
+            Ok(&self.buffer[self.position..])
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    const EPS: f32 = 1e-6;
+    /// Drops the bytes whose consumption was deferred.
+    #[inline(always)]
+    fn settle(&mut self) {
+        if self.deferred > 0 {
+            self.settle_deferred();
+        }
+    }
 
-    #[test]
-    fn simple_strings() {
-        let text = "Howdy neighbour, how are you doing?";
-        let mut stream = InputStream::new(text.as_bytes());
+    #[cold]
+    fn settle_deferred(&mut self) {
+        let deferred = self.deferred;
+        self.deferred = 0;
+        self.advance(deferred);
+    }
 
-        let first: String = stream.scan().expect("First string");
-        let second: String = stream.scan().expect("Second string");
-        let third: String = stream.scan().expect("Third string");
-        assert_eq!(first, "Howdy");
-        assert_eq!(second, "neighbour,");
-        assert_eq!(third, "how");
+    #[inline(always)]
+    fn advance(&mut self, amount: usize) {
+        if self.position == self.buffer.len() {
+            self.reader.consume(amount)
+        } else {
+            self.position += amount;
+            if self.position == self.buffer.len() {
+                self.buffer.clear();
+                self.position = 0;
+            }
+        }
     }
 
-    #[test]
-    fn simple_numbers() {
-        let text = "5 -7 12.5 -2.85";
-        let mut stream = InputStream::new(text.as_bytes());
-        assert_eq!(5, stream.scan().expect("5"));
-        assert_eq!(-7, stream.scan().expect("-7"));
-        assert_eq!(
-            true,
-            (12.5 - stream.scan::<f32>().expect("12.5")).abs() < EPS
-        );
-        assert_eq!(
-            true,
-            (-2.85 - stream.scan::<f32>().expect("-2.85")).abs() < EPS
-        );
+    /// Hands `bytes` back so that they are the next ones to be read.
+    fn unread(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.settle();
+        let newlines = if self.track_lines {
+            count_newlines(bytes)
+        } else {
+            0
+        };
+        self.uncount(bytes.len() as u64, newlines);
+        let rest = self.buffer.split_off(self.position);
+        self.buffer.clear();
+        self.buffer.extend_from_slice(bytes);
+        self.buffer.extend_from_slice(&rest);
+        self.position = 0;
     }
 
-    #[test]
-    fn newlines() {
-        let text = "12\nHello";
-        let mut stream = InputStream::new(text.as_bytes());
-        assert_eq!(12, stream.scan().expect("12"));
-        assert_eq!("Hello", stream.scan::<String>().expect("Hello"));
+    /// Hands back `bytes` for a char pushed back by the user, remembering when it is read.
+    fn push_back(&mut self, bytes: &[u8]) {
+        self.pushed_back = Some((self.consumed, self.owed));
+        self.unread(bytes);
     }
 
-    #[test]
-    fn test_non_utf8() {
-        let text: [u8; 1] = [255];
-        let mut stream = InputStream::new(&text[..]);
-        assert_eq!(true, stream.scan::<i32>().is_err());
+    /// Whether a char handed back by `push_back` has not been read again yet.
+    fn push_back_pending(&self) -> bool {
+        self.pushed_back
+            .is_some_and(|(consumed, owed)| self.consumed + owed < consumed + self.owed)
     }
+}
 
-    #[test]
-    fn test_not_parsing() {
-        let text = "hello";
-        let mut stream = InputStream::new(text.as_bytes());
-        assert_eq!(true, stream.scan::<i32>().is_err());
+impl<T: BufRead + Seek> Pushback<T> {
+    /// Seeks the reader to `position`, dropping any bytes handed back.
+    fn seek_to(&mut self, position: u64) -> io::Result<()> {
+        self.deferred = 0;
+        let _ = self.reader.seek(SeekFrom::Start(position))?;
+        self.buffer.clear();
+        self.position = 0;
+        self.pushed_back = None;
+        Ok(())
     }
+}
 
-    #[test]
-    fn test_limit_buffer() {
-        let text = "25 150 -250";
+impl<T: BufRead> Read for Pushback<T> {
+    #[inline(always)]
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.settle();
+        if self.position == self.buffer.len() {
+            let amount = self.reader.read(buffer)?;
+            let newlines = if self.track_lines {
+                count_newlines(&buffer[..amount])
+            } else {
+                0
+            };
+            self.count(amount as u64, newlines);
+            return Ok(amount);
+        }
+        let amount = (&self.buffer[self.position..]).read(buffer)?;
+        self.consume(amount);
+        Ok(amount)
+    }
+}
+
+impl<T: BufRead> BufRead for Pushback<T> {
+    #[inline(always)]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.settle();
+        self.window()
+    }
+
+    #[inline(always)]
+    fn consume(&mut self, amount: usize) {
+        // Like for any `BufRead`, this is only called after `fill_buf`, which already settled.
+        let newlines = self.count_lines(amount);
+        self.count(amount as u64, newlines);
+        self.advance(amount);
+    }
+}
+
+fn count_newlines(bytes: &[u8]) -> u64 {
+    bytes.iter().filter(|&&c| c == b'\n').count() as u64
+}
+
+#[inline(always)]
+fn is_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\x09'..=b'\x0d')
+}
+
+#[inline(always)]
+fn act_while<T, F, G, E>(reader: &mut Pushback<T>, mut condition: F, act: G) -> Result<(), Error<E>>
+where
+    T: BufRead,
+    F: FnMut(&&u8) -> bool,
+    G: FnMut(&[u8]) -> Result<(), Error<E>>,
+{
+    act_until(reader, |buf| buf.iter().position(|c| !condition(&c)), act)
+}
+
+/// Like `act_while`, but `find` returns where the run of bytes ends in each buffer, if it does.
+#[inline(always)]
+fn act_until<T, F, G, E>(reader: &mut Pushback<T>, mut find: F, mut act: G) -> Result<(), Error<E>>
+where
+    T: BufRead,
+    F: FnMut(&[u8]) -> Option<usize>,
+    G: FnMut(&[u8]) -> Result<(), Error<E>>,
+{
+    loop {
+        let (skipped, done) = {
+            let buf = reader.fill_buf_retrying()?;
+            let skipped = find(buf).unwrap_or(buf.len());
+            act(&buf[..skipped])?;
+            (skipped, skipped < buf.len() || buf.is_empty())
+        };
+
+        reader.consume(skipped);
+        if done {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the position of the first `needle` in `buf`.
+#[cfg(not(feature = "memchr"))]
+#[inline(always)]
+fn find_byte(needle: u8, buf: &[u8]) -> Option<usize> {
+    buf.iter().position(|&c| c == needle)
+}
+
+/// Returns the position of the first `needle` in `buf`, searching for it with `memchr`.
+#[cfg(feature = "memchr")]
+#[inline(always)]
+fn find_byte(needle: u8, buf: &[u8]) -> Option<usize> {
+    memchr::memchr(needle, buf)
+}
+
+#[inline(always)]
+fn peek_byte<T: BufRead>(reader: &mut Pushback<T>) -> io::Result<Option<u8>> {
+    Ok(reader.fill_buf_retrying()?.first().cloned())
+}
+
+/// Reads a single UTF-8 encoded char, which may straddle buffer refills. Returns `None` at the end
+/// of the reader.
+fn read_char<T: BufRead, E>(reader: &mut Pushback<T>) -> Result<Option<char>, Error<E>> {
+    let (bytes, read) = read_char_bytes(reader)?;
+    decode_char(&bytes[..read])
+}
+
+/// Decodes the bytes read by `read_char_bytes`, which are `None` if there are none.
+fn decode_char<E>(bytes: &[u8]) -> Result<Option<char>, Error<E>> {
+    Ok(str::from_utf8(bytes)?.chars().next())
+}
+
+/// Reads the bytes of a single UTF-8 encoded char, stopping early at a byte that can't continue
+/// it, and returns them along with how many there are, which is `0` at the end of the reader.
+fn read_char_bytes<T: BufRead>(reader: &mut Pushback<T>) -> io::Result<([u8; 4], usize)> {
+    let first = match peek_byte(reader)? {
+        Some(first) => first,
+        None => return Ok(([0; 4], 0)),
+    };
+    let width = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+
+    let mut bytes = [first, 0, 0, 0];
+    reader.consume(1);
+    let mut read = 1;
+    while read < width {
+        match peek_byte(reader)? {
+            Some(c @ 0x80..=0xbf) => {
+                bytes[read] = c;
+                reader.consume(1);
+                read += 1;
+            }
+            _ => break,
+        }
+    }
+    Ok((bytes, read))
+}
+
+/// Like `read_char`, but an invalid sequence is read as `U+FFFD`. As in `String::from_utf8_lossy`,
+/// each maximal prefix of a valid sequence is replaced by a single `U+FFFD`, and the byte that
+/// broke it off is left to start the next char.
+fn read_char_lossy<T: BufRead>(reader: &mut Pushback<T>) -> io::Result<Option<char>> {
+    let first = match peek_byte(reader)? {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    reader.consume(1);
+    // The valid range of the second byte depends on the first, which rules out overlong
+    // encodings, surrogates and chars above U+10FFFF.
+    let (width, second) = match first {
+        0x00..=0x7f => return Ok(Some(char::from(first))),
+        0xc2..=0xdf => (2, 0x80..=0xbf),
+        0xe0 => (3, 0xa0..=0xbf),
+        0xe1..=0xec | 0xee..=0xef => (3, 0x80..=0xbf),
+        0xed => (3, 0x80..=0x9f),
+        0xf0 => (4, 0x90..=0xbf),
+        0xf1..=0xf3 => (4, 0x80..=0xbf),
+        0xf4 => (4, 0x80..=0x8f),
+        _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+    };
+
+    let mut bytes = [first, 0, 0, 0];
+    for (read, byte) in bytes.iter_mut().enumerate().take(width).skip(1) {
+        let range = if read == 1 {
+            second.clone()
+        } else {
+            0x80..=0xbf
+        };
+        match peek_byte(reader)? {
+            Some(c) if range.contains(&c) => {
+                *byte = c;
+                reader.consume(1);
+            }
+            _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+        }
+    }
+    Ok(str::from_utf8(&bytes[..width])
+        .ok()
+        .and_then(|c| c.chars().next())
+        .or(Some(char::REPLACEMENT_CHARACTER)))
+}
+
+/// Like `read_char`, but the end of the reader is an `UnexpectedEof` error.
+fn expect_char<T: BufRead, E>(reader: &mut Pushback<T>) -> Result<char, Error<E>> {
+    read_char(reader)?.ok_or_else(|| Error::Io(io::ErrorKind::UnexpectedEof.into()))
+}
+
+/// Reads the rest of a Rust escape sequence, after its `\`, and returns the char it stands for.
+fn read_escape<T: BufRead, E>(reader: &mut Pushback<T>) -> Result<char, Error<E>> {
+    let invalid = |found: String| Error::Unexpected {
+        found,
+        expected: "an escape sequence".to_string(),
+    };
+    let hex = |digits: &str| {
+        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            u32::from_str_radix(digits, 16).ok()
+        } else {
+            None
+        }
+    };
+    Ok(match expect_char(reader)? {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '0' => '\0',
+        '\\' => '\\',
+        '\'' => '\'',
+        '"' => '"',
+        'x' => {
+            let digits: String = [expect_char(reader)?, expect_char(reader)?]
+                .iter()
+                .collect();
+            match hex(&digits) {
+                Some(value) if value <= 0x7f => value as u8 as char,
+                _ => return Err(invalid(format!("\\x{}", digits))),
+            }
+        }
+        'u' => {
+            let open = expect_char(reader)?;
+            if open != '{' {
+                return Err(invalid(format!("\\u{}", open)));
+            }
+            let mut digits = String::new();
+            loop {
+                match expect_char(reader)? {
+                    '}' => break,
+                    c if digits.len() < 6 => digits.push(c),
+                    c => return Err(invalid(format!("\\u{{{}{}", digits, c))),
+                }
+            }
+            match hex(&digits).and_then(char::from_u32) {
+                Some(c) => c,
+                None => return Err(invalid(format!("\\u{{{}}}", digits))),
+            }
+        }
+        c => return Err(invalid(format!("\\{}", c))),
+    })
+}
+
+fn parse_lenient_bool(token: &[u8]) -> Option<bool> {
+    const TRUE: [&[u8]; 3] = [b"true", b"yes", b"1"];
+    const FALSE: [&[u8]; 3] = [b"false", b"no", b"0"];
+    if TRUE.iter().any(|word| word.eq_ignore_ascii_case(token)) {
+        Some(true)
+    } else if FALSE.iter().any(|word| word.eq_ignore_ascii_case(token)) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[inline(always)]
+fn parse_slice<F: FromStr>(token: &[u8]) -> Result<F, Error<F::Err>> {
+    let token = match token.split_last() {
+        Some((&b' ', token)) => token,
+        _ => token,
+    };
+
+    str::from_utf8(token)?.parse().map_err(Error::FromStr)
+}
+
+/// Checks that the digits of `number`, after an optional sign, are grouped in thousands by
+/// `group`: one to three digits in the first group and exactly three in every following one.
+/// A number without any grouping character is well grouped as well.
+fn is_well_grouped(number: &str, group: char) -> bool {
+    let digits = number.strip_prefix(['+', '-']).unwrap_or(number);
+    let mut groups = digits.split(group);
+    let first = groups.next().map_or(0, str::len);
+    !digits.contains(group) || ((1..=3).contains(&first) && groups.all(|group| group.len() == 3))
+}
+
+#[inline(always)]
+fn split_tokens(text: &str) -> LineTokens<'_> {
+    let is_separator: fn(char) -> bool = |c| c.is_ascii() && is_whitespace(c as u8);
+    let is_token: fn(&&str) -> bool = |token| !token.is_empty();
+    LineTokens {
+        tokens: text.split(is_separator).filter(is_token),
+    }
+}
+
+impl<T: BufRead> InputStream<T> {
+    /// Creates an instance of InputStream which wraps the given
+    /// [`std::io::BufRead`](https://doc.rust-lang.org/std/io/trait.BufRead.html).
+    #[inline(always)]
+    pub fn new(reader: T) -> InputStream<T> {
+        InputStream {
+            reader: Pushback::new(reader),
+            byte_buffer: Vec::new(),
+            delimiters: Delimiters::default(),
+            keep_empty_fields: false,
+            skip_trailing_delimiter: false,
+            after_token: false,
+        }
+    }
+
+    /// Creates an instance of InputStream which wraps the given
+    /// [`std::io::BufRead`](https://doc.rust-lang.org/std/io/trait.BufRead.html) and separates
+    /// values by any of the given `delimiters` instead of whitespace.
+    ///
+    /// Only the given bytes are delimiters, so include `b'\n'` if values may also end a line.
+    pub fn with_delimiter_set(reader: T, delimiters: &[u8]) -> InputStream<T> {
+        InputStream {
+            delimiters: Delimiters::new(delimiters),
+            ..InputStream::new(reader)
+        }
+    }
+
+    /// Gives up with a [`TimedOut`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O
+    /// error once `n` reads in a row came back empty, instead of taking an empty read as the end
+    /// of the input.
+    ///
+    /// A [`BufRead`](https://doc.rust-lang.org/std/io/trait.BufRead.html) signals the end of its
+    /// input with an empty buffer, so by default, with `n` being `0`, the first empty read ends
+    /// the input. Streaming readers that come back empty while waiting for more data would end
+    /// scanning early that way; with `n` set, empty reads are retried, and a reader that stays
+    /// empty fails instead of looping forever. As an empty read can't be told apart from the end
+    /// of the input, reaching the end of such a reader is reported as that error too.
+    pub fn set_max_empty_fills(&mut self, n: usize) {
+        self.reader.max_empty_fills = n;
+    }
+
+    /// Limits how many times in a row a read that was interrupted
+    /// ([`ErrorKind::Interrupted`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html)) is
+    /// retried before the interruption is returned as an error.
+    ///
+    /// `None`, the default, retries forever.
+    pub fn set_interrupt_retries(&mut self, max: Option<usize>) {
+        self.reader.interrupt_retries = max;
+    }
+
+    /// Sets whether the stream keeps count of the lines it consumed, for
+    /// [`line_number`](#method.line_number), which is off by default.
+    ///
+    /// Counting takes another pass over every consumed byte. Lines are only counted while this
+    /// is on, so turn it on before reading anything for line numbers to match the input.
+    pub fn track_lines(&mut self, track: bool) {
+        self.reader.track_lines = track;
+    }
+
+    /// Returns the 1-based number of the line the stream is on, that is one more than the
+    /// number of `\n` bytes consumed while [`track_lines`](#method.track_lines) was on.
+    pub fn line_number(&self) -> usize {
+        self.reader.newlines as usize + 1
+    }
+
+    /// Sets whether consecutive delimiters enclose empty values instead of being skipped
+    /// together, which is off by default.
+    ///
+    /// When on, only the single delimiter ending the previous value is skipped, so with `,` as
+    /// the delimiter `a,,b` scans as `a`, an empty string and `b`, and `,a` starts with an empty
+    /// string. This is mostly useful with [`with_delimiter_set`](#method.with_delimiter_set)
+    /// for inputs like CSV where empty cells are meaningful.
+    ///
+    /// Iterators and collecting methods such as [`scan_all`](#method.scan_all) yield the empty
+    /// values too, and only stop at the end of the input. A delimiter right before the end of
+    /// the input doesn't start another, empty value.
+    pub fn keep_empty_fields(&mut self, keep: bool) {
+        self.keep_empty_fields = keep;
+    }
+
+    /// Sets whether a delimiter right before the end of a line is tolerated as a trailing
+    /// separator, which is off by default.
+    ///
+    /// This only matters with [`keep_empty_fields`](#method.keep_empty_fields) on, where with
+    /// `;` and `\n` as delimiters `1;2;3;\n` would otherwise hold an empty fourth value between
+    /// the last `;` and the `\n`. When on, such a delimiter is skipped along with the newline
+    /// after it, so the line scans as exactly three values. Without `keep_empty_fields`
+    /// consecutive delimiters are skipped together anyway, trailing ones included.
+    pub fn skip_trailing_delimiter(&mut self, skip: bool) {
+        self.skip_trailing_delimiter = skip;
+    }
+
+    /// Scan the underlying buffered reader for a value of a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html)
+    /// returning a [`Result`](type.Result.html).
+    ///
+    /// An example on how to use scan is at the [`crate documentation`](index.html).
+    pub fn scan<F: FromStr>(&mut self) -> Result<F> {
+        self.inner_scan(None)
+    }
+
+    /// Scan a value like [`scan`](#method.scan), returning an
+    /// [`anyhow::Result`](https://docs.rs/anyhow/1/anyhow/type.Result.html) for application code
+    /// that uses `anyhow`.
+    ///
+    /// Every error gets the name of the type being scanned as context, and parse and I/O errors
+    /// are kept as the underlying cause, so they can still be downcast to.
+    #[cfg(feature = "anyhow")]
+    pub fn scan_anyhow<F>(&mut self) -> anyhow::Result<F>
+    where
+        F: FromStr,
+        F::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let context = || format!("Could not scan a `{}`", std::any::type_name::<F>());
+        self.scan().map_err(|err| {
+            match err {
+                Error::Io(err) => anyhow::Error::new(err),
+                Error::FromStr(err) => anyhow::Error::new(err),
+                err => anyhow::Error::new(err),
+            }
+            .context(context())
+        })
+    }
+
+    /// Scan the underlying buffer reader for a value of a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html)
+    /// returning a [`Result`](type.Result.html).
+    ///
+    /// This is a refined version of [`scan`](struct.InputStream.html#method.scan) which allows
+    /// limits to be placed on the maximum size of the internal buffer
+    ///
+    /// The limit counts every byte of the token, a sign included, so `-250` needs a limit of at
+    /// least `4` while `250` fits in `3`. A longer token fails with
+    /// [`Error::BufferLimitExceeded`](enum.Error.html#variant.BufferLimitExceeded) but is still
+    /// consumed whole, so the stream stays usable from the next value on.
+    pub fn scan_with_limit<F: FromStr>(&mut self, limit: usize) -> Result<F> {
+        self.inner_scan(Some(limit))
+    }
+
+    /// Scan the underlying buffered reader for a value of a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html), separating
+    /// values by the bytes for which `is_delimiter` returns `true` instead of whitespace.
+    pub fn scan_with_delimiter<F, D>(&mut self, is_delimiter: D) -> Result<F>
+    where
+        F: FromStr,
+        D: Fn(u8) -> bool,
+    {
+        self.read_token_by(is_delimiter)?;
+        self.parse_token()
+    }
+
+    /// Scan a value like [`scan_with_delimiter`](#method.scan_with_delimiter) with `delim` as the
+    /// only delimiter, such as `,` for CSV-style inputs.
+    ///
+    /// The end of the token is found in one search per buffer window, using `memchr` when the
+    /// `memchr` feature is on, instead of testing a predicate byte by byte, which pays off for
+    /// large inputs with long fields. Leading occurrences of `delim` are skipped.
+    pub fn scan_delimited_fast<F: FromStr>(&mut self, delim: u8) -> Result<F> {
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ..
+        } = self;
+        act_while(reader, |&&c| c == delim, |_| Ok(()))?;
+        byte_buffer.clear();
+        act_until(
+            reader,
+            |buf| find_byte(delim, buf),
+            |slice| {
+                byte_buffer.extend_from_slice(slice);
+                Ok(())
+            },
+        )?;
+        self.parse_token()
+    }
+
+    /// Scan the next word, splitting on ASCII punctuation as well as whitespace, so
+    /// `hello,world!` is scanned as `hello` followed by `world`.
+    ///
+    /// The apostrophe is not a delimiter, which keeps words like `don't` whole. Returns an empty
+    /// string at the end of the input, like `scan::<String>()`.
+    pub fn scan_word(&mut self) -> Result<String> {
+        self.read_token_by(|c| is_whitespace(c) || (c.is_ascii_punctuation() && c != b'\''))?;
+        self.parse_token()
+    }
+
+    /// Scan a string and return it lowercased, according to Unicode's rules.
+    pub fn scan_lower(&mut self) -> Result<String> {
+        self.read_token(None)?;
+        Ok(str::from_utf8(&self.byte_buffer)?.to_lowercase())
+    }
+
+    /// Scan a string and return it uppercased, according to Unicode's rules.
+    pub fn scan_upper(&mut self) -> Result<String> {
+        self.read_token(None)?;
+        Ok(str::from_utf8(&self.byte_buffer)?.to_uppercase())
+    }
+
+    /// Scan a string and strip any of the given `chars` from both of its ends, so `[42]` scanned
+    /// with `&['[', ']']` gives `42`.
+    pub fn scan_trimmed(&mut self, chars: &[char]) -> Result<String> {
+        self.read_token(None)?;
+        Ok(str::from_utf8(&self.byte_buffer)?
+            .trim_matches(chars)
+            .to_string())
+    }
+
+    /// Scan a single token and split it by `sep`, parsing every piece as a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html), so `1,2,3`
+    /// split by `','` gives `[1, 2, 3]`.
+    ///
+    /// Empty pieces, from consecutive separators, are parsed like any other piece, which is an
+    /// error for most types other than `String`. Returns an empty vector at the end of the input.
+    pub fn scan_split<F: FromStr>(&mut self, sep: char) -> Result<Vec<F>, Error<F::Err>> {
+        if !self.next_token()? {
+            return Ok(Vec::new());
+        }
+        str::from_utf8(&self.byte_buffer)?
+            .split(sep)
+            .map(|piece| piece.parse().map_err(Error::FromStr))
+            .collect()
+    }
+
+    /// Like `read_token`, but separating tokens by the bytes matching `is_delimiter`.
+    #[inline(always)]
+    fn read_token_by<D, E>(&mut self, is_delimiter: D) -> Result<(), Error<E>>
+    where
+        D: Fn(u8) -> bool,
+    {
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ..
+        } = self;
+        act_while(reader, |&&c| is_delimiter(c), |_| Ok(()))?;
+        byte_buffer.clear();
+        act_while(
+            reader,
+            |&&c| !is_delimiter(c),
+            |slice| {
+                byte_buffer.extend_from_slice(slice);
+                Ok(())
+            },
+        )
+    }
+
+    /// Returns an iterator scanning values of a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html) until the end
+    /// of the input.
+    pub fn scan_iter<F: FromStr>(&mut self) -> ScanIter<'_, T, F> {
+        ScanIter {
+            stream: self,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the remaining tokens, as strings.
+    ///
+    /// This is the same as `scan_iter::<String>()`.
+    pub fn tokens(&mut self) -> ScanIter<'_, T, String> {
+        self.scan_iter()
+    }
+
+    /// Scan every remaining token and count how many times each of them appears.
+    ///
+    /// Tokens are not all kept in memory, only the distinct ones. Invalid utf8 is reported as an
+    /// [`InvalidData`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn word_counts(&mut self) -> io::Result<HashMap<String, usize>> {
+        let mut counts = HashMap::new();
+        for token in self.tokens() {
+            *counts.entry(token.map_err(into_io_error)?).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Returns an iterator scanning values like [`scan_iter`](#method.scan_iter) and
+    /// transforming each of them with `g` as it is read.
+    ///
+    /// Errors are passed through unchanged.
+    pub fn scan_map_iter<'a, F, U, G>(
+        &'a mut self,
+        mut g: G,
+    ) -> impl Iterator<Item = Result<U, Error<F::Err>>> + 'a
+    where
+        F: FromStr + 'a,
+        G: FnMut(F) -> U + 'a,
+    {
+        self.scan_iter().map(move |value| value.map(&mut g))
+    }
+
+    /// Scan every remaining value and send each of them over `tx`, consuming the stream.
+    ///
+    /// The first error is sent as well and ends the scan, as does the receiving end hanging up.
+    /// Nothing is spawned here: the stream is taken by value so that a parsing thread can own it,
+    /// as in `thread::spawn(move || stream.scan_to_channel(tx))`, which requires the reader to be
+    /// `Send`, while worker threads receive the values.
+    pub fn scan_to_channel<F>(mut self, tx: Sender<Result<F>>)
+    where
+        F: FromStr + Send + 'static,
+        F::Err: Send,
+    {
+        for value in self.scan_iter() {
+            let failed = value.is_err();
+            if tx.send(value).is_err() || failed {
+                return;
+            }
+        }
+    }
+
+    /// Scan every remaining value of a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html) into a vector.
+    pub fn scan_all<F: FromStr>(&mut self) -> Result<Vec<F>, Error<F::Err>> {
+        Vec::scan_from(self)
+    }
+
+    /// Scan every remaining value and fold them into an accumulator with `g`, starting from
+    /// `init`, such as to sum them without collecting them in a vector first.
+    ///
+    /// The first error aborts the fold and is returned.
+    pub fn scan_fold<F, A, G>(&mut self, init: A, mut g: G) -> Result<A, Error<F::Err>>
+    where
+        F: FromStr,
+        G: FnMut(A, F) -> A,
+    {
+        let mut accumulator = init;
+        loop {
+            if !self.next_token()? {
+                return Ok(accumulator);
+            }
+            accumulator = g(accumulator, self.parse_token()?);
+        }
+    }
+
+    /// Scan every remaining value and hand each of them to `g`, stopping cleanly at the end of
+    /// the input.
+    ///
+    /// Unlike a `while let Ok(value) = stream.scan()` loop, which also stops on the first bad
+    /// value without telling, the first parse or I/O error is returned here.
+    pub fn for_each_value<F: FromStr, G: FnMut(F)>(
+        &mut self,
+        mut g: G,
+    ) -> Result<(), Error<F::Err>> {
+        self.scan_fold((), |(), value| g(value))
+    }
+
+    /// Scan every remaining value like [`for_each_value`](#method.for_each_value), keeping only
+    /// the first occurrence of each of them, in the order they were first seen.
+    ///
+    /// Duplicates are found by hashing rather than by sorting, so the values keep their order
+    /// and large inputs are not sorted. Only the hashes of the unique values are stored besides
+    /// the values themselves, which are compared in place on a hash collision.
+    pub fn scan_unique<F: FromStr + Eq + Hash>(&mut self) -> Result<Vec<F>, Error<F::Err>> {
+        let state = RandomState::new();
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+        self.scan_fold(Vec::new(), |mut unique: Vec<F>, value: F| {
+            let same_hash = seen.entry(state.hash_one(&value)).or_default();
+            if !same_hash.iter().any(|&index| unique[index] == value) {
+                same_hash.push(unique.len());
+                unique.push(value);
+            }
+            unique
+        })
+    }
+
+    /// Scan the next token and parse it with `parse`, which has mutable access to a context such
+    /// as a string interner or an arena.
+    ///
+    /// Errors returned by `parse` are wrapped in
+    /// [`Error::FromStr`](enum.Error.html#variant.FromStr).
+    pub fn scan_with_ctx<C, F, P>(
+        &mut self,
+        ctx: &mut C,
+        parse: P,
+    ) -> Result<F, Error<Box<dyn std::error::Error>>>
+    where
+        P: FnOnce(&mut C, &str) -> std::result::Result<F, Box<dyn std::error::Error>>,
+    {
+        self.read_token(None)?;
+        parse(ctx, str::from_utf8(&self.byte_buffer)?).map_err(Error::FromStr)
+    }
+
+    /// Scan `n` values of a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html) into a vector.
+    ///
+    /// Reaching the end of the input before `n` values were scanned is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_n<F: FromStr>(&mut self, n: usize) -> Result<Vec<F>, Error<F::Err>> {
+        self.scan_collect(n)
+    }
+
+    /// Scan `count` values like [`scan_n`](#method.scan_n) into a `SmallVec`, which keeps up to
+    /// `N` of them on the stack and only allocates when `count` is larger.
+    ///
+    /// This avoids an allocation per group in tight loops reading many small groups, such as
+    /// the three vertices of each triangle in a mesh.
+    #[cfg(feature = "smallvec")]
+    pub fn scan_smallvec<F: FromStr, const N: usize>(
+        &mut self,
+        count: usize,
+    ) -> Result<smallvec::SmallVec<[F; N]>, Error<F::Err>> {
+        self.scan_collect(count)
+    }
+
+    /// Scan two values, which may be of different types as long as their parse errors are the
+    /// same, as for two integer types.
+    ///
+    /// Reaching the end of the input before both values were scanned is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_pair<A, B>(&mut self) -> Result<(A, B), Error<A::Err>>
+    where
+        A: FromStr,
+        B: FromStr<Err = A::Err>,
+    {
+        Ok((self.scan_value()?, self.scan_value()?))
+    }
+
+    /// Scan `n` pairs like [`scan_pair`](#method.scan_pair) into two parallel vectors, one with
+    /// every first value and one with every second value. As for `scan_pair`, the two types
+    /// must have the same parse error.
+    ///
+    /// The first error aborts the whole scan, so the vectors always have the same length.
+    #[allow(clippy::type_complexity)]
+    pub fn scan_columns<A, B>(&mut self, n: usize) -> Result<(Vec<A>, Vec<B>), Error<A::Err>>
+    where
+        A: FromStr,
+        B: FromStr<Err = A::Err>,
+    {
+        let mut columns = (Vec::with_capacity(n), Vec::with_capacity(n));
+        for _ in 0..n {
+            let (a, b) = self.scan_pair()?;
+            columns.0.push(a);
+            columns.1.push(b);
+        }
+        Ok(columns)
+    }
+
+    /// Scan a 2D point, that is two values of the same type, like
+    /// [`scan_pair`](#method.scan_pair).
+    pub fn scan_point<F: FromStr>(&mut self) -> Result<(F, F), Error<F::Err>> {
+        self.scan_pair()
+    }
+
+    /// Scan a 3D point, that is three values of the same type, like
+    /// [`scan_point`](#method.scan_point).
+    pub fn scan_point3<F: FromStr>(&mut self) -> Result<(F, F, F), Error<F::Err>> {
+        Ok((self.scan_value()?, self.scan_value()?, self.scan_value()?))
+    }
+
+    /// Scan a count of type `C`, such as `usize`, followed by that many values of a type that
+    /// implements [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html).
+    ///
+    /// A count that does not parse, or that does not fit in a `usize` (like a negative one), is
+    /// reported as an [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_counted<F, C>(&mut self) -> Result<Vec<F>, Error<F::Err>>
+    where
+        F: FromStr,
+        C: FromStr + TryInto<usize>,
+    {
+        if !self.next_token()? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let count = str::from_utf8(&self.byte_buffer)?
+            .parse::<C>()
+            .ok()
+            .and_then(|count| count.try_into().ok());
+        match count {
+            Some(count) => self.scan_n(count),
+            None => Err(self.unexpected("a count")),
+        }
+    }
+
+    /// Scan the dimensions of a grid, `rows` then `cols`, followed by the `rows * cols` values of
+    /// the grid, row by row.
+    ///
+    /// Both dimensions are read from the stream like any other value. One that is not a `usize`
+    /// is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected), as are dimensions whose
+    /// product overflows a `usize`. Reaching the end of the input before the grid is complete is
+    /// an [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    ///
+    /// A grid with no columns holds no values and is returned without any rows, however many
+    /// it claims to have.
+    pub fn scan_grid_with_dims<F: FromStr>(&mut self) -> Result<Vec<Vec<F>>, Error<F::Err>> {
+        let rows = self.scan_size("a grid dimension")?;
+        let cols = self.scan_size("a grid dimension")?;
+        if rows.checked_mul(cols).is_none() {
+            return Err(Error::Unexpected {
+                found: format!("{} {}", rows, cols),
+                expected: "grid dimensions whose product fits in a `usize`".to_string(),
+            });
+        }
+        if cols == 0 {
+            return Ok(Vec::new());
+        }
+        (0..rows).map(|_| self.scan_n(cols)).collect()
+    }
+
+    /// Scan `pairs` run-length encoded pairs of a count and a value, such as `3 7` for three
+    /// `7`s, and expand them into a vector.
+    ///
+    /// If `max_len` is set, a run that would make the vector longer than it fails with
+    /// [`Error::BufferLimitExceeded`](enum.Error.html#variant.BufferLimitExceeded) before
+    /// anything is allocated for it, so a huge count can't exhaust memory. A count that is not
+    /// a nonnegative integer is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_rle<F: FromStr + Clone>(
+        &mut self,
+        pairs: usize,
+        max_len: Option<usize>,
+    ) -> Result<Vec<F>, Error<F::Err>> {
+        let mut values = Vec::new();
+        for _ in 0..pairs {
+            let count = self.scan_size("a count")?;
+            if max_len.is_some_and(|max_len| count > max_len - values.len()) {
+                return Err(Error::BufferLimitExceeded);
+            }
+            let value = self.scan_value()?;
+            values.extend(std::iter::repeat_n(value, count));
+        }
+        Ok(values)
+    }
+
+    /// Scan a record of `names.len()` fields written as `key{sep}value` tokens, in any order,
+    /// into a map from each key to its value.
+    ///
+    /// Every key must be one of `names` and appear once. A token without `sep`, with any other
+    /// key or with a key seen before is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected). If the input ends
+    /// before every field was found, the names of the missing ones are listed in an
+    /// [`Error::MissingFields`](enum.Error.html#variant.MissingFields).
+    pub fn scan_named_fields(
+        &mut self,
+        names: &[&str],
+        sep: u8,
+    ) -> Result<HashMap<String, String>, Error<Infallible>> {
+        let mut fields = HashMap::with_capacity(names.len());
+        for _ in 0..names.len() {
+            if !self.next_token()? {
+                break;
+            }
+            let token = str::from_utf8(&self.byte_buffer)?;
+            let (key, value) = match token.split_once(char::from(sep)) {
+                Some(field) => field,
+                None => {
+                    let expected = format!("a `key{}value` field", char::from(sep));
+                    return Err(self.unexpected(&expected));
+                }
+            };
+            let reject = |expected: String| Error::Unexpected {
+                found: key.to_string(),
+                expected,
+            };
+            if !names.contains(&key) {
+                return Err(reject(format!("a field named one of {}", names.join(", "))));
+            }
+            if fields.contains_key(key) {
+                return Err(reject(format!("each of {} at most once", names.join(", "))));
+            }
+            let _ = fields.insert(key.to_string(), value.to_string());
+        }
+
+        if fields.len() < names.len() {
+            let missing = names.iter().filter(|&&name| !fields.contains_key(name));
+            return Err(Error::MissingFields(
+                missing.map(|&name| name.to_string()).collect(),
+            ));
+        }
+        Ok(fields)
+    }
+
+    /// Scans a size such as a grid dimension, naming it `expected` if it is not one.
+    fn scan_size<E>(&mut self, expected: &str) -> Result<usize, Error<E>> {
+        if !self.next_token()? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        match str::from_utf8(&self.byte_buffer)?.parse() {
+            Ok(dimension) => Ok(dimension),
+            Err(_) => Err(self.unexpected(expected)),
+        }
+    }
+
+    /// Scan `n` values like [`scan_n`](#method.scan_n), collecting them into any collection
+    /// such as a `HashSet` or a `VecDeque`.
+    ///
+    /// The first error aborts the whole collection.
+    pub fn scan_collect<F, C>(&mut self, n: usize) -> Result<C, Error<F::Err>>
+    where
+        F: FromStr,
+        C: FromIterator<F>,
+    {
+        (0..n).map(|_| self.scan_value()).collect()
+    }
+
+    /// Scan `n` values like [`scan_n`](#method.scan_n), checking that each of them is strictly
+    /// greater than the one before, as for sorted timestamps.
+    ///
+    /// The first value that isn't is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected)
+    /// holding it and naming its index.
+    pub fn scan_increasing<F: FromStr + PartialOrd>(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<F>, Error<F::Err>> {
+        self.scan_ordered(n, true)
+    }
+
+    /// Like [`scan_increasing`](#method.scan_increasing), but each value only has to be greater
+    /// than or equal to the one before.
+    pub fn scan_nondecreasing<F: FromStr + PartialOrd>(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<F>, Error<F::Err>> {
+        self.scan_ordered(n, false)
+    }
+
+    fn scan_ordered<F: FromStr + PartialOrd>(
+        &mut self,
+        n: usize,
+        strict: bool,
+    ) -> Result<Vec<F>, Error<F::Err>> {
+        let mut values: Vec<F> = Vec::with_capacity(n);
+        for index in 0..n {
+            let value = self.scan_value()?;
+            if let Some(previous) = values.last() {
+                let ordered = if strict {
+                    value > *previous
+                } else {
+                    value >= *previous
+                };
+                if !ordered {
+                    let order = if strict { "above" } else { "at least" };
+                    return Err(self.unexpected(&format!(
+                        "a value {} the previous one at index {}",
+                        order, index
+                    )));
+                }
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    /// Scan `n` values like [`scan_n`](#method.scan_n) and return them sorted in ascending
+    /// order, also removing duplicates if `dedup` is set.
+    ///
+    /// The sort is stable, so values that compare equal keep the order they were read in, and
+    /// deduplication keeps the first of every run of equal values. The result may hence be
+    /// shorter than `n` when `dedup` is set.
+    pub fn scan_sorted<F: FromStr + Ord>(
+        &mut self,
+        n: usize,
+        dedup: bool,
+    ) -> Result<Vec<F>, Error<F::Err>> {
+        let mut values = self.scan_n(n)?;
+        values.sort();
+        if dedup {
+            values.dedup();
+        }
+        Ok(values)
+    }
+
+    /// Scan `n` values and add each of them to its count in `map`, so counts can be accumulated
+    /// over several reads.
+    ///
+    /// The first error aborts the tally, leaving the values counted so far in `map`. Reaching
+    /// the end of the input before `n` values were scanned is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn tally_into<F: FromStr + Eq + Hash>(
+        &mut self,
+        map: &mut HashMap<F, usize>,
+        n: usize,
+    ) -> Result<(), Error<F::Err>> {
+        for _ in 0..n {
+            *map.entry(self.scan_value()?).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+
+    /// Scan `out.len()` values of a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html) into `out`.
+    ///
+    /// This is the allocation free version of [`scan_n`](struct.InputStream.html#method.scan_n).
+    /// On error the values scanned so far are left in place at the start of `out`.
+    pub fn scan_slice<F: FromStr>(&mut self, out: &mut [F]) -> Result<(), Error<F::Err>> {
+        for slot in out {
+            *slot = self.scan_value()?;
+        }
+        Ok(())
+    }
+
+    /// Scan values until a blank line or the end of the input, returning all of them.
+    ///
+    /// A blank line is one that only contains whitespace. Whitespace, blank lines included, is
+    /// skipped before the first value, so consecutive calls read consecutive blocks.
+    pub fn scan_vec_until_blank<F: FromStr>(&mut self) -> Result<Vec<F>, Error<F::Err>> {
+        let mut values = Vec::new();
+        loop {
+            let mut newlines = 0;
+            act_while(
+                &mut self.reader,
+                |&&c| is_whitespace(c),
+                |slice| {
+                    newlines += slice.iter().filter(|&&c| c == b'\n').count();
+                    Ok(())
+                },
+            )?;
+            if newlines >= 2 && !values.is_empty() {
+                return Ok(values);
+            }
+
+            if !self.next_token()? {
+                return Ok(values);
+            }
+            values.push(self.parse_token()?);
+        }
+    }
+
+    /// Like `scan`, but reaching the end of the input is an error instead of an empty token.
+    #[inline(always)]
+    fn scan_value<F: FromStr>(&mut self) -> Result<F> {
+        if !self.next_token()? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        self.parse_token()
+    }
+
+    #[inline(always)]
+    fn inner_scan<F: FromStr>(&mut self, limit: Option<usize>) -> Result<F> {
+        // Fast path: when the token lies entirely inside the current buffer window it can be
+        // parsed right out of it, without being copied into `byte_buffer` first.
+        if !self.keep_empty_fields {
+            let &mut InputStream {
+                ref mut reader,
+                ref delimiters,
+                ref mut after_token,
+                ..
+            } = self;
+            let buf = reader.fill_buf_retrying()?;
+            if let Some(start) = buf.iter().position(|&c| !delimiters.contains(c)) {
+                if let Some(length) = delimiters.find(&buf[start..]) {
+                    if limit.is_none_or(|limit| length <= limit) {
+                        let result = parse_slice(&buf[start..start + length]);
+                        reader.consume(start + length);
+                        *after_token = true;
+                        return result;
+                    }
+                }
+            }
+        }
+
+        self.read_token(limit)?;
+        self.parse_token()
+    }
+
+    /// Skips leading delimiters and fills `byte_buffer` with the next token.
+    #[inline(always)]
+    fn read_token<E>(&mut self, limit: Option<usize>) -> Result<(), Error<E>> {
+        self.skip_delimiters()?;
+        self.collect_token(limit)
+    }
+
+    /// Like `read_token`, but returns whether there was a token, as opposed to the end of the
+    /// input. When empty fields are kept, an empty token is still a token.
+    #[inline(always)]
+    fn next_token<E>(&mut self) -> Result<bool, Error<E>> {
+        self.read_token(None)?;
+        Ok(self.token_present()?)
+    }
+
+    /// Whether the token just collected in `byte_buffer` is one, rather than the end of the
+    /// input. An empty token is only a field, kept when empty fields are, if a delimiter ends it.
+    #[inline(always)]
+    fn token_present(&mut self) -> io::Result<bool> {
+        if !self.byte_buffer.is_empty() {
+            return Ok(true);
+        }
+        Ok(self.keep_empty_fields && peek_byte(&mut self.reader)?.is_some())
+    }
+
+    #[inline(always)]
+    fn skip_delimiters<E>(&mut self) -> Result<(), Error<E>> {
+        self.skip_delimiters_with(|_| {})
+    }
+
+    /// Like `skip_delimiters`, handing every skipped byte to `skipped`.
+    #[inline(always)]
+    fn skip_delimiters_with<E, G: FnMut(&[u8])>(&mut self, mut skipped: G) -> Result<(), Error<E>> {
+        let delimiters = &self.delimiters;
+        if self.keep_empty_fields {
+            if self.after_token {
+                self.after_token = false;
+                let delimiter = peek_byte(&mut self.reader)?.filter(|&c| delimiters.contains(c));
+                if let Some(delimiter) = delimiter {
+                    self.reader.consume(1);
+                    skipped(&[delimiter]);
+                    if self.skip_trailing_delimiter
+                        && delimiter != b'\n'
+                        && delimiters.contains(b'\n')
+                        && peek_byte(&mut self.reader)? == Some(b'\n')
+                    {
+                        self.reader.consume(1);
+                        skipped(b"\n");
+                    }
+                }
+            }
+            return Ok(());
+        }
+        act_while(
+            &mut self.reader,
+            |&&c| delimiters.contains(c),
+            |slice| {
+                skipped(slice);
+                Ok(())
+            },
+        )
+    }
+
+    /// Fills `byte_buffer` with the bytes up to the next delimiter.
+    ///
+    /// A token longer than `limit` is consumed whole before failing with `BufferLimitExceeded`.
+    #[inline(always)]
+    fn collect_token<E>(&mut self, limit: Option<usize>) -> Result<(), Error<E>> {
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ref delimiters,
+            ref mut after_token,
+            ..
+        } = self;
+        byte_buffer.clear();
+        *after_token = true;
+        let mut exceeded = false;
+        act_until(
+            reader,
+            |buf| delimiters.find(buf),
+            |slice| {
+                if limit.is_some_and(|limit| byte_buffer.len() + slice.len() > limit) {
+                    exceeded = true;
+                } else if !exceeded {
+                    byte_buffer.extend_from_slice(slice);
+                }
+                Ok(())
+            },
+        )?;
+        if exceeded {
+            byte_buffer.clear();
+            return Err(Error::BufferLimitExceeded);
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn parse_token<F: FromStr>(&self) -> Result<F> {
+        parse_slice(&self.byte_buffer)
+    }
+
+    /// Scan a boolean which must be exactly `true` or `false`.
+    ///
+    /// Anything else is reported as an [`Error::Unexpected`](enum.Error.html#variant.Unexpected)
+    /// holding the offending token.
+    pub fn scan_bool_strict(&mut self) -> Result<bool> {
+        self.read_token(None)?;
+        match self.byte_buffer.as_slice() {
+            b"true" => Ok(true),
+            b"false" => Ok(false),
+            _ => Err(self.unexpected("`true` or `false`")),
+        }
+    }
+
+    /// Scan a boolean leniently, accepting `true`/`false`, `yes`/`no` and `1`/`0`, ignoring
+    /// case.
+    ///
+    /// Anything else is reported as an [`Error::Unexpected`](enum.Error.html#variant.Unexpected)
+    /// holding the offending token.
+    pub fn scan_bool(&mut self) -> Result<bool> {
+        self.read_token(None)?;
+        match parse_lenient_bool(&self.byte_buffer) {
+            Some(value) => Ok(value),
+            None => Err(self.unexpected("a boolean")),
+        }
+    }
+
+    /// Scan an integer used as a flag: `0` is `false` and any nonzero integer is `true`.
+    ///
+    /// The integer may have a sign and any number of digits, so `-1` and
+    /// `99999999999999999999` are both `true`, while `-0` and `000` are `false`. A token that is
+    /// not an integer is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_flag(&mut self) -> Result<bool> {
+        self.read_token(None)?;
+        let token = &self.byte_buffer;
+        let digits = token
+            .strip_prefix(b"+")
+            .or_else(|| token.strip_prefix(b"-"));
+        let digits = digits.unwrap_or(token);
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+            return Err(self.unexpected("an integer flag"));
+        }
+        Ok(digits.iter().any(|&c| c != b'0'))
+    }
+
+    /// Scan a percentage such as `42%`, returning it as a fraction (`0.42`).
+    ///
+    /// A number without a trailing `%` is returned as it is, so `0.42` also scans as `0.42`
+    /// while `42` scans as `42.0`.
+    pub fn scan_percent(&mut self) -> Result<f64> {
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        match token.strip_suffix('%') {
+            Some(percent) => Ok(percent.parse::<f64>().map_err(Error::FromStr)? / 100.0),
+            None => token.parse().map_err(Error::FromStr),
+        }
+    }
+
+    /// Scan a float like `scan::<f64>()`, also accepting the infinities and NaNs written by C
+    /// libraries and other tools.
+    ///
+    /// Like `scan`, the case-insensitive `inf`, `infinity` and `nan` are accepted with an optional
+    /// `+` or `-` sign. On top of those, and with the same signs and case insensitivity:
+    ///
+    /// - `∞` and `1.#INF` are infinities;
+    /// - `1.#QNAN`, `1.#SNAN`, `1.#IND` and `nan(...)`, such as `nan(0x7ff8)` or `-nan(ind)`,
+    ///   are NaNs.
+    pub fn scan_float_special(&mut self) -> Result<f64> {
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        let (sign, magnitude) = match token.strip_prefix(['+', '-']) {
+            Some(magnitude) => (&token[..1], magnitude),
+            None => ("", token),
+        };
+        let magnitude = magnitude.to_ascii_lowercase();
+        let normalized = match magnitude.as_str() {
+            "∞" | "1.#inf" => "inf",
+            "1.#qnan" | "1.#snan" | "1.#ind" => "nan",
+            nan if nan.starts_with("nan(") && nan.ends_with(')') => "nan",
+            _ => return token.parse().map_err(Error::FromStr),
+        };
+        format!("{}{}", sign, normalized)
+            .parse()
+            .map_err(Error::FromStr)
+    }
+
+    /// Scan a number of seconds such as `90` or `1.5` as a
+    /// [`Duration`](https://doc.rust-lang.org/std/time/struct.Duration.html), keeping fractions of
+    /// a second.
+    ///
+    /// A negative number, a NaN or one too large for a `Duration` is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_seconds(&mut self) -> Result<Duration, Error<num::ParseFloatError>> {
+        let seconds = self.scan_value()?;
+        Duration::try_from_secs_f64(seconds)
+            .map_err(|_| self.unexpected("a nonnegative number of seconds"))
+    }
+
+    /// Scan one of the nonzero integer types, such as
+    /// [`NonZeroU32`](https://doc.rust-lang.org/std/num/struct.NonZeroU32.html).
+    ///
+    /// A zero is reported as [`Error::Zero`](enum.Error.html#variant.Zero), distinct from a
+    /// token that is not an integer at all.
+    pub fn scan_nonzero<F: NonZeroInteger>(&mut self) -> Result<F, Error<ParseIntError>> {
+        F::new(self.scan()?).ok_or(Error::Zero)
+    }
+
+    /// Scan an unsigned integer, which must not have a sign.
+    ///
+    /// A leading `+` or `-` is reported as [`Error::Signed`](enum.Error.html#variant.Signed),
+    /// distinct from a token that is not a number at all. Note that `scan` accepts a leading `+`
+    /// for both signed and unsigned types.
+    pub fn scan_uint<F: Unsigned>(&mut self) -> Result<F> {
+        self.read_token(None)?;
+        match self.byte_buffer.first() {
+            Some(&sign @ b'+') | Some(&sign @ b'-') => Err(Error::Signed(char::from(sign))),
+            _ => self.parse_token(),
+        }
+    }
+
+    /// Scan a string like `scan::<String>()`, reporting invalid utf8 as an
+    /// [`Error::Utf8Token`](enum.Error.html#variant.Utf8Token) which holds the raw bytes of the
+    /// token and the offset within it where decoding failed.
+    pub fn scan_checked_utf8(&mut self) -> Result<String> {
+        self.read_token(None)?;
+        match str::from_utf8(&self.byte_buffer) {
+            Ok(token) => Ok(token.to_string()),
+            Err(error) => Err(Error::Utf8Token {
+                token: self.byte_buffer.clone(),
+                error,
+            }),
+        }
+    }
+
+    /// Scan a value whose characters must all satisfy `allowed`, such as an identifier that may
+    /// only hold alphanumeric characters, and then parse it like [`scan`](#method.scan).
+    ///
+    /// The first character of the token that is not allowed is reported as an
+    /// [`Error::InvalidChar`](enum.Error.html#variant.InvalidChar), along with its byte offset
+    /// within the token, before any parsing is attempted.
+    pub fn scan_validated<F: FromStr, P: Fn(char) -> bool>(&mut self, allowed: P) -> Result<F> {
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        if let Some((position, found)) = token.char_indices().find(|&(_, c)| !allowed(c)) {
+            return Err(Error::InvalidChar { found, position });
+        }
+        self.parse_token()
+    }
+
+    /// Scan an integer whose digits may be grouped in thousands by `group`, like `1,234,567`
+    /// when `group` is `','`.
+    ///
+    /// Grouping is checked: the first group, after an optional sign, has one to three digits and
+    /// every following group exactly three, otherwise an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected) is returned. An integer without
+    /// any grouping character is accepted as well. As the number is read as a single token,
+    /// `group` can't be one of the stream's delimiters, which is an
+    /// [`InvalidInput`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error. A
+    /// stream built with [`with_delimiter_set`](#method.with_delimiter_set) can read `-12 345`
+    /// with `' '` as `group`, as long as the space is not a delimiter.
+    pub fn scan_int_grouped(&mut self, group: char) -> Result<i64> {
+        if group.is_ascii() && self.delimiters.contains(group as u8) {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the grouping character {:?} is a delimiter", group),
+            )));
+        }
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        if !is_well_grouped(token, group) {
+            return Err(self.unexpected("an integer grouped in thousands"));
+        }
+        token
+            .chars()
+            .filter(|&c| c != group)
+            .collect::<String>()
+            .parse()
+            .map_err(Error::FromStr)
+    }
+
+    /// Scan a float written with `decimal` as its decimal mark and digits optionally grouped in
+    /// thousands by `grouping`, like `1.234,56` in German with `','` and `Some('.')`.
+    ///
+    /// The token is rewritten into the form `f64`'s parser expects. Grouping is checked like in
+    /// [`scan_int_grouped`](#method.scan_int_grouped), and is only allowed before the decimal
+    /// mark, while a `.` that is neither the decimal mark nor the grouping character is rejected
+    /// rather than read as one. Both of those are an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected). `decimal` and `grouping` must
+    /// differ, otherwise an [`InvalidInput`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html)
+    /// I/O error is returned without reading anything.
+    pub fn scan_float_fmt(&mut self, decimal: char, grouping: Option<char>) -> Result<f64> {
+        if grouping == Some(decimal) {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{:?} is both the decimal mark and the grouping character",
+                    decimal
+                ),
+            )));
+        }
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        let (integer, fraction) = token.split_once(decimal).unwrap_or((token, ""));
+        let well_formed = grouping
+            .is_none_or(|group| is_well_grouped(integer, group) && !fraction.contains(group));
+        let stray_dot = |part: &str| part.contains('.') && grouping != Some('.');
+        if !well_formed || (decimal != '.' && (stray_dot(integer) || stray_dot(fraction))) {
+            return Err(self.unexpected("a float in the expected locale format"));
+        }
+
+        let mut canonical: String = integer.chars().filter(|&c| Some(c) != grouping).collect();
+        if token.contains(decimal) {
+            canonical.push('.');
+            canonical.push_str(fraction);
+        }
+        canonical.parse().map_err(Error::FromStr)
+    }
+
+    /// Scan a complex number like `3+4i`, `-2-5i`, `7i` or `3`, returning its real and imaginary
+    /// parts.
+    ///
+    /// The accepted grammar is a real part, an imaginary part, or a real part directly followed
+    /// by a signed imaginary part, without spaces. The real part is any float `f64` parses, and
+    /// the imaginary part is an optionally signed float followed by `i`, where a missing float
+    /// means `1`, so `i`, `-i` and `2-i` are all accepted. A `+` or `-` right after an `e` or `E`
+    /// belongs to an exponent, as in `1e-3+2e+1i`. Anything else fails to parse as a float.
+    pub fn scan_complex(&mut self) -> Result<(f64, f64), Error<num::ParseFloatError>> {
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        let imaginary = match token.strip_suffix('i') {
+            Some(imaginary) => imaginary,
+            None => return Ok((token.parse().map_err(Error::FromStr)?, 0.0)),
+        };
+
+        let bytes = imaginary.as_bytes();
+        let split = (1..bytes.len())
+            .rev()
+            .find(|&k| matches!(bytes[k], b'+' | b'-') && !matches!(bytes[k - 1], b'e' | b'E'))
+            .unwrap_or(0);
+        let (real, imaginary) = imaginary.split_at(split);
+        let real = match real {
+            "" => 0.0,
+            real => real.parse().map_err(Error::FromStr)?,
+        };
+        let imaginary = match imaginary {
+            "" | "+" => 1.0,
+            "-" => -1.0,
+            imaginary => imaginary.parse().map_err(Error::FromStr)?,
+        };
+        Ok((real, imaginary))
+    }
+
+    /// Scan an integer with an optional radix prefix, `0x` for hexadecimal, `0o` for octal or
+    /// `0b` for binary, itself after an optional sign, like `-0x1f` or `+0b101`.
+    ///
+    /// A token without a prefix is read as decimal. The sign is applied while parsing, so the
+    /// minimum of a signed type is accepted, like `-0x80` for an `i8`, while a value out of range
+    /// or a `-` on an unsigned type fails to parse. A second sign after the prefix is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_radix_int<F: RadixInteger>(&mut self) -> Result<F> {
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        let (sign, unsigned) = match token.strip_prefix('-') {
+            Some(unsigned) => ("-", unsigned),
+            None => ("", token.strip_prefix('+').unwrap_or(token)),
+        };
+        let (radix, digits) = match unsigned.get(..2) {
+            Some("0x") | Some("0X") => (16, &unsigned[2..]),
+            Some("0o") | Some("0O") => (8, &unsigned[2..]),
+            Some("0b") | Some("0B") => (2, &unsigned[2..]),
+            _ => (10, unsigned),
+        };
+        if digits.starts_with(['+', '-']) {
+            return Err(self.unexpected("an integer with a single sign"));
+        }
+        F::from_str_radix(&format!("{}{}", sign, digits), radix).map_err(Error::FromStr)
+    }
+
+    /// Scan a dotted version such as `1.2.3`, returning its major, minor and patch numbers.
+    ///
+    /// Missing trailing components are `0`, so `1.2` gives `(1, 2, 0)` and `4` gives `(4, 0, 0)`.
+    /// More than three components, or one that is not made of ASCII digits, is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected), while one too large for a
+    /// `u64` fails to parse.
+    pub fn scan_version(&mut self) -> Result<(u64, u64, u64), Error<ParseIntError>> {
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        let components: Vec<&str> = token.split('.').collect();
+        let well_formed = components.len() <= 3
+            && components
+                .iter()
+                .all(|part| !part.is_empty() && part.bytes().all(|c| c.is_ascii_digit()));
+        if !well_formed {
+            return Err(self.unexpected("a version like `1.2.3`"));
+        }
+
+        let mut version = [0; 3];
+        for (number, part) in version.iter_mut().zip(components) {
+            *number = part.parse().map_err(Error::FromStr)?;
+        }
+        Ok((version[0], version[1], version[2]))
+    }
+
+    /// Scan a hex color such as `#1a2b3c`, returning its red, green and blue components.
+    ///
+    /// The leading `#` is optional and the 3 digit shorthand is accepted, so `#abc` is the same
+    /// as `#aabbcc`. Anything else is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_color(&mut self) -> Result<(u8, u8, u8), Error<ParseIntError>> {
+        self.read_token(None)?;
+        let token = self
+            .byte_buffer
+            .strip_prefix(b"#")
+            .unwrap_or(&self.byte_buffer);
+        if !token.iter().all(u8::is_ascii_hexdigit) || (token.len() != 3 && token.len() != 6) {
+            return Err(self.unexpected("a color like `#rrggbb` or `#rgb`"));
+        }
+
+        let width = token.len() / 3;
+        let component = |index: usize| -> Result<u8, Error<ParseIntError>> {
+            let digits = str::from_utf8(&token[index * width..(index + 1) * width])?;
+            let value = u8::from_str_radix(digits, 16).map_err(Error::FromStr)?;
+            Ok(if width == 1 { value * 0x11 } else { value })
+        };
+        Ok((component(0)?, component(1)?, component(2)?))
+    }
+
+    /// Scan a socket address such as `127.0.0.1:8080` or `[::1]:8080`.
+    ///
+    /// A valid IP address without a port, bracketed or not, is reported as an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected) pointing out the missing port,
+    /// while any other bad address is reported by `SocketAddr`'s own parser.
+    pub fn scan_socket_addr(&mut self) -> Result<SocketAddr> {
+        self.read_token(None)?;
+        let token = str::from_utf8(&self.byte_buffer)?;
+        token.parse().map_err(|err| {
+            let address = token
+                .strip_prefix('[')
+                .and_then(|token| token.strip_suffix(']'))
+                .unwrap_or(token);
+            if address.parse::<IpAddr>().is_ok() {
+                self.unexpected("an address followed by a `:port`")
+            } else {
+                Error::FromStr(err)
+            }
+        })
+    }
+
+    /// Scan an exclusive range written like `3..7`.
+    ///
+    /// A token without `..` between its bounds, including an inclusive range like `3..=7`, is
+    /// an [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_range<F: FromStr>(&mut self) -> Result<Range<F>, Error<F::Err>> {
+        self.read_token(None)?;
+        let (start, end) = self.range_bounds("..", "a range like `a..b`")?;
+        Ok(start..end)
+    }
+
+    /// Scan an inclusive range written like `3..=7`.
+    ///
+    /// A token without `..=` between its bounds is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_range_inclusive<F: FromStr>(&mut self) -> Result<RangeInclusive<F>, Error<F::Err>> {
+        self.read_token(None)?;
+        let (start, end) = self.range_bounds("..=", "a range like `a..=b`")?;
+        Ok(start..=end)
+    }
+
+    /// Splits the token in `byte_buffer` around `separator` and parses both bounds.
+    fn range_bounds<F: FromStr>(
+        &self,
+        separator: &str,
+        expected: &str,
+    ) -> Result<(F, F), Error<F::Err>> {
+        let token = str::from_utf8(&self.byte_buffer)?;
+        match token.split_once(separator) {
+            Some((start, end)) if !end.starts_with('=') => Ok((
+                start.parse().map_err(Error::FromStr)?,
+                end.parse().map_err(Error::FromStr)?,
+            )),
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    /// Scan a value which must be one of the `allowed` strings, then parse it as a type that
+    /// implements [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html).
+    ///
+    /// A token outside of `allowed` is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected) listing all of the options,
+    /// which is clearer than the error of most `FromStr` implementations for unknown variants.
+    pub fn scan_enum<F: FromStr>(&mut self, allowed: &[&str]) -> Result<F> {
+        self.read_token(None)?;
+        if !allowed
+            .iter()
+            .any(|option| option.as_bytes() == self.byte_buffer.as_slice())
+        {
+            let options: Vec<String> = allowed
+                .iter()
+                .map(|option| format!("`{}`", option))
+                .collect();
+            return Err(self.unexpected(&format!("one of {}", options.join(", "))));
+        }
+        self.parse_token()
+    }
+
+    /// Scan the next token as a string, which must match `re` as a whole, such as an identifier
+    /// or a date.
+    ///
+    /// The first match of `re` in the token must span all of it, not just a substring, so
+    /// `[a-z]+` rejects `abc1`. A token that does not match is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected), and reaching the end of the
+    /// input is an [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O
+    /// error.
+    ///
+    /// `re` is used as it was built, options such as case insensitivity included. As its
+    /// alternations are tried leftmost-first, `a|ab` matches only `a` of the token `ab` and
+    /// rejects it, so longer alternatives should come first, as in `ab|a`, or the pattern be
+    /// anchored with `^(?:...)$`.
+    #[cfg(feature = "regex")]
+    pub fn scan_regex(&mut self, re: &regex::Regex) -> Result<String> {
+        if !self.next_token()? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let token = str::from_utf8(&self.byte_buffer)?;
+        match re.find(token) {
+            Some(found) if found.start() == 0 && found.end() == token.len() => {
+                Ok(token.to_string())
+            }
+            _ => Err(self.unexpected(&format!("a token matching `{}`", re.as_str()))),
+        }
+    }
+
+    /// Scan the text up to the start of the next match of `re`, which is left in the stream, such
+    /// as to split free-form text on a complex separator.
+    ///
+    /// Nothing is skipped first. A match right at the start gives an empty string and consumes
+    /// nothing, so the separator has to be consumed before scanning the next piece, while if
+    /// `re` never matches the rest of the input is returned. The input is read one buffer window
+    /// at a time and a match is only accepted once it ends before the data read so far does, so
+    /// it can not grow any longer. The leftmost match is thus found unless an earlier one would
+    /// need text from further ahead than a match that is already complete.
+    ///
+    /// Everything up to the match is held in memory, and as a match may start anywhere in it and
+    /// need text that was not read yet, the whole of it is searched again after every window.
+    /// Finding a separator `n` bytes ahead thus costs `O(n * n / w)` for a window of `w` bytes,
+    /// so a larger [`BufReader`](https://doc.rust-lang.org/std/io/struct.BufReader.html)
+    /// capacity is worth it when the pieces may be long.
+    #[cfg(feature = "regex")]
+    pub fn scan_until_regex(&mut self, re: &regex::Regex) -> Result<String> {
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ..
+        } = self;
+        byte_buffer.clear();
+        loop {
+            let buf = reader.fill_buf_retrying()?;
+            let end = buf.is_empty();
+            byte_buffer.extend_from_slice(buf);
+            let amount = buf.len();
+            reader.consume(amount);
+
+            let text = match str::from_utf8(byte_buffer) {
+                Ok(text) => text,
+                // A char may straddle the end of the window.
+                Err(err) if err.error_len().is_none() && !end => {
+                    str::from_utf8(&byte_buffer[..err.valid_up_to()])?
+                }
+                Err(err) => return Err(err.into()),
+            };
+            match re.find(text) {
+                Some(found) if end || found.end() < text.len() => {
+                    let start = found.start();
+                    let before = text[..start].to_string();
+                    reader.unread(&byte_buffer[start..]);
+                    return Ok(before);
+                }
+                _ if end => return Ok(text.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Stream every digit of the next token to `visitor`, returning how many there were, without
+    /// ever holding the whole token in memory.
+    ///
+    /// This suits huge numbers, such as a bignum with a hundred thousand digits. A byte in the
+    /// token that is not an ASCII digit is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected), though the rest of the token is
+    /// still consumed. Returns `0` at the end of the input.
+    pub fn scan_digits<G: FnMut(u8)>(&mut self, mut visitor: G) -> Result<usize> {
+        self.skip_delimiters()?;
+        let delimiters = &self.delimiters;
+        let mut count = 0;
+        let mut invalid = None;
+        act_while(
+            &mut self.reader,
+            |&&c| !delimiters.contains(c),
+            |slice| {
+                for &c in slice {
+                    if invalid.is_some() {
+                        break;
+                    } else if c.is_ascii_digit() {
+                        visitor(c);
+                        count += 1;
+                    } else {
+                        invalid = Some(c);
+                    }
+                }
+                Ok(())
+            },
+        )?;
+
+        match invalid {
+            Some(c) => Err(Error::Unexpected {
+                found: String::from_utf8_lossy(&[c]).into_owned(),
+                expected: "a digit".to_string(),
+            }),
+            None => Ok(count),
+        }
+    }
+
+    /// Scan a value that may be missing, as marked by the `none_marker` token, such as `-`, `NA`
+    /// or `NULL` in tabular data.
+    ///
+    /// Returns `None` for a token that is exactly `none_marker`, compared case-sensitively, and
+    /// otherwise parses the token like [`scan`](#method.scan).
+    pub fn scan_optional<F: FromStr>(
+        &mut self,
+        none_marker: &str,
+    ) -> Result<Option<F>, Error<F::Err>> {
+        self.read_token(None)?;
+        if self.byte_buffer == none_marker.as_bytes() {
+            return Ok(None);
+        }
+        self.parse_token().map(Some)
+    }
+
+    /// Scan a token made of `0` and `1` characters into a vector of booleans, so `0110` gives
+    /// `[false, true, true, false]`.
+    ///
+    /// Any other character is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_bits(&mut self) -> Result<Vec<bool>, Error<Infallible>> {
+        self.read_token(None)?;
+        self.byte_buffer
+            .iter()
+            .map(|&c| match c {
+                b'0' => Ok(false),
+                b'1' => Ok(true),
+                _ => Err(self.unexpected("a string of `0` and `1`")),
+            })
+            .collect()
+    }
+
+    /// Scan the next token and check that it is exactly `literal`, such as a `BEGIN` keyword.
+    ///
+    /// The token is consumed either way, and a mismatch is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected). See
+    /// [`consume_literal`](#method.consume_literal) for a version that leaves a mismatching token
+    /// in the stream.
+    pub fn expect_literal(&mut self, literal: &str) -> Result<(), Error<Infallible>> {
+        self.read_token(None)?;
+        if self.byte_buffer != literal.as_bytes() {
+            return Err(self.unexpected(&format!("`{}`", literal)));
+        }
+        Ok(())
+    }
+
+    /// Consume the next token only if it is exactly `literal`, returning whether it was.
+    ///
+    /// On a mismatch nothing is consumed, leading whitespace included. The end of the input is
+    /// never a match, so an empty `literal` never matches.
+    pub fn consume_literal(&mut self, literal: &str) -> io::Result<bool> {
+        let skipped = self.peek_token()?;
+        let token = &self.byte_buffer[skipped..];
+        let matches = !token.is_empty() && token == literal.as_bytes();
+        if matches {
+            self.reader.consume(self.byte_buffer.len());
+            self.after_token = true;
+        }
+        Ok(matches)
+    }
+
+    /// Consume the next bytes only if they are exactly `prefix`, returning whether they were.
+    ///
+    /// Unlike [`consume_literal`](#method.consume_literal) no whitespace is skipped and the
+    /// prefix does not have to be a whole token, which suits literal markers in the middle of a
+    /// format. On a mismatch nothing is consumed, even when the prefix straddles buffer refills.
+    pub fn consume_prefix(&mut self, prefix: &str) -> io::Result<bool> {
+        let prefix = prefix.as_bytes();
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ..
+        } = self;
+        byte_buffer.clear();
+        while byte_buffer.len() < prefix.len() {
+            let buf = match reader.fill_buf_retrying() {
+                Ok(buf) => buf,
+                Err(err) => {
+                    reader.unread(byte_buffer);
+                    return Err(err);
+                }
+            };
+            let expected = &prefix[byte_buffer.len()..];
+            let amount = expected.len().min(buf.len());
+            if amount == 0 || buf[..amount] != expected[..amount] {
+                reader.unread(byte_buffer);
+                return Ok(false);
+            }
+            byte_buffer.extend_from_slice(&buf[..amount]);
+            reader.consume(amount);
+        }
+        Ok(true)
+    }
+
+    /// Builds an [`Error::Unexpected`](enum.Error.html#variant.Unexpected) for the token in
+    /// `byte_buffer`.
+    fn unexpected<E>(&self, expected: &str) -> Error<E> {
+        Error::Unexpected {
+            found: String::from_utf8_lossy(&self.byte_buffer).into_owned(),
+            expected: expected.to_string(),
+        }
+    }
+
+    /// Scan a value like [`scan`](#method.scan), feeding every byte it consumes into `hasher`.
+    ///
+    /// This includes the delimiters skipped before the token, so scanning a whole input this way
+    /// hashes exactly the bytes that were processed, apart from the delimiters after the last
+    /// value.
+    pub fn scan_with_checksum<F: FromStr, H: Hasher>(&mut self, hasher: &mut H) -> Result<F> {
+        self.skip_delimiters_with(|skipped| hasher.write(skipped))?;
+        self.collect_token(None)?;
+        hasher.write(&self.byte_buffer);
+        self.parse_token()
+    }
+
+    /// Scan a value like [`scan`](#method.scan), also returning the range of byte offsets
+    /// `[start, end)` it occupied in the stream.
+    ///
+    /// Offsets are counted like [`bytes_consumed`](#method.bytes_consumed).
+    pub fn scan_spanned<F: FromStr>(&mut self) -> Result<(F, Range<u64>), Error<F::Err>> {
+        self.skip_delimiters()?;
+        let start = self.bytes_consumed();
+        self.collect_token(None)?;
+        let end = self.bytes_consumed();
+        Ok((self.parse_token()?, start..end))
+    }
+
+    /// Scan a value like [`scan`](#method.scan), also returning the 1-based number of the line it
+    /// is on, as given by [`line_number`](#method.line_number).
+    ///
+    /// This needs [`track_lines`](#method.track_lines) to be on.
+    pub fn scan_with_line<F: FromStr>(&mut self) -> Result<(F, usize), Error<F::Err>> {
+        self.skip_delimiters()?;
+        let line = self.line_number();
+        self.collect_token(None)?;
+        Ok((self.parse_token()?, line))
+    }
+
+    /// Scan a value like [`scan`](#method.scan), also returning the token it was parsed from,
+    /// which keeps details the value loses, such as leading zeros.
+    pub fn scan_echo<F: FromStr>(&mut self) -> Result<(F, String), Error<F::Err>> {
+        self.read_token(None)?;
+        let value = self.parse_token()?;
+        Ok((value, str::from_utf8(&self.byte_buffer)?.to_string()))
+    }
+
+    /// Returns an iterator over the remaining tokens like [`tokens`](#method.tokens), along with
+    /// the range of byte offsets each of them occupied, as given by
+    /// [`scan_spanned`](#method.scan_spanned).
+    ///
+    /// The range starts at the first byte of the token and ends one past its last byte, no
+    /// matter how the token straddles the buffer refills of the reader.
+    pub fn tokens_with_positions(
+        &mut self,
+    ) -> impl Iterator<Item = Result<(Range<u64>, String), Error<Infallible>>> + '_ {
+        std::iter::from_fn(move || match self.scan_spanned::<String>() {
+            Ok((token, span)) => match self.token_present() {
+                Ok(true) => Some(Ok((span, token))),
+                Ok(false) => None,
+                Err(err) => Some(Err(Error::Io(err))),
+            },
+            Err(err) => Some(Err(err)),
+        })
+    }
+
+    /// Returns how many bytes were consumed from the underlying buffered reader since the stream
+    /// was created, not counting bytes that were only peeked at.
+    pub fn bytes_consumed(&self) -> u64 {
+        self.reader.consumed
+    }
+
+    /// Scan the raw bytes of the next token, along with the delimiter byte that ended it, or
+    /// `None` if the token ended at the end of the input.
+    ///
+    /// The delimiter is not consumed. No utf8 validation is done on the token.
+    pub fn scan_raw(&mut self) -> io::Result<(Vec<u8>, Option<u8>)> {
+        if let Err(Error::Io(err)) = self.read_token::<()>(None) {
+            return Err(err);
+        }
+        let delimiter = peek_byte(&mut self.reader)?;
+        Ok((self.byte_buffer.clone(), delimiter))
+    }
+
+    /// Scan the next token as a string, borrowing it from the reader's buffer when it can.
+    ///
+    /// When the token and the delimiter ending it are both in the current buffer window, the
+    /// token is returned as `Cow::Borrowed` without being copied. Otherwise, as when it straddles
+    /// a refill or ends at the end of the input, it is returned as `Cow::Owned`. Like `scan`, an
+    /// empty string is returned at the end of the input.
+    pub fn scan_cow(&mut self) -> Result<Cow<'_, str>, Error<Infallible>> {
+        let in_window = if self.keep_empty_fields {
+            None
+        } else {
+            let &mut InputStream {
+                ref mut reader,
+                ref delimiters,
+                ..
+            } = self;
+            let buf = reader.fill_buf_retrying()?;
+            buf.iter()
+                .position(|&c| !delimiters.contains(c))
+                .and_then(|start| Some((start, start + delimiters.find(&buf[start..])?)))
+        };
+
+        if let Some((start, end)) = in_window {
+            self.reader.consume_deferred(end);
+            self.after_token = true;
+            return Ok(Cow::Borrowed(str::from_utf8(
+                &self.reader.window()?[start..end],
+            )?));
+        }
+
+        self.read_token(None)?;
+        Ok(Cow::Owned(str::from_utf8(&self.byte_buffer)?.to_string()))
+    }
+
+    /// Scan the next token as a string into `buf`, which is cleared first, returning it borrowed
+    /// from `buf` rather than from the stream.
+    ///
+    /// As the string is not tied to the stream, it can be kept while scanning goes on, such as
+    /// to compare consecutive tokens read into two buffers in turn. Like `scan`, an empty string
+    /// is returned at the end of the input.
+    pub fn scan_str_into<'a>(
+        &mut self,
+        buf: &'a mut Vec<u8>,
+    ) -> Result<&'a str, Error<Infallible>> {
+        mem::swap(&mut self.byte_buffer, buf);
+        let result = self.read_token(None);
+        mem::swap(&mut self.byte_buffer, buf);
+        result?;
+        Ok(str::from_utf8(buf)?)
+    }
+
+    /// Peek at the next value and report whether it parses as a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html), without
+    /// consuming anything.
+    ///
+    /// Returns `false` if there are no more values. The stream is left exactly as it was before
+    /// the call, leading whitespace included.
+    pub fn next_is<F: FromStr>(&mut self) -> io::Result<bool> {
+        let skipped = self.peek_token()?;
+        let token = &self.byte_buffer[skipped..];
+        Ok(
+            !token.is_empty()
+                && str::from_utf8(token).is_ok_and(|token| token.parse::<F>().is_ok()),
+        )
+    }
+
+    /// Return how many bytes the next token occupies, without consuming anything, so that an
+    /// oversized token can be rejected before it is read. Returns `0` at the end of the input.
+    ///
+    /// The token and the delimiters before it are buffered in full to be measured, however many
+    /// refills of the reader it takes, and then handed back to the stream.
+    pub fn peek_token_len(&mut self) -> io::Result<usize> {
+        let skipped = self.peek_token()?;
+        Ok(self.byte_buffer.len() - skipped)
+    }
+
+    /// Report whether the next token starts on a new line, meaning that a `\n` comes before it,
+    /// which lets token based parsers detect where a row ends.
+    ///
+    /// The delimiters before the next token are consumed, but the token itself is not, so a
+    /// following `scan` reads it as usual. Consecutive calls return `false` after the first,
+    /// as the newline has been consumed by then. At the end of the input this reports whether
+    /// the trailing delimiters held a newline.
+    pub fn next_token_on_new_line(&mut self) -> io::Result<bool> {
+        let delimiters = &self.delimiters;
+        let mut newline = false;
+        let result: Result<(), Error<()>> = act_while(
+            &mut self.reader,
+            |&&c| delimiters.contains(c),
+            |slice| {
+                newline = newline || slice.contains(&b'\n');
+                Ok(())
+            },
+        );
+        match result {
+            Err(Error::Io(err)) => Err(err),
+            _ => Ok(newline),
+        }
+    }
+
+    /// Scan a value like [`scan`](#method.scan), along with whether it was the last one, meaning
+    /// that only delimiters follow it.
+    ///
+    /// The check peeks ahead without consuming anything after the value.
+    pub fn scan_with_eof_flag<F: FromStr>(&mut self) -> Result<(F, bool), Error<F::Err>> {
+        let value = self.inner_scan(None)?;
+        let skipped = self.peek_token()?;
+        Ok((value, self.byte_buffer.len() == skipped))
+    }
+
+    /// Scan a value like [`scan`](#method.scan) and check that it is the last one, for inputs
+    /// that must hold a single value, such as a file with just one number.
+    ///
+    /// Only delimiters may follow the value. Anything else is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected) holding the next token, which
+    /// is left in the stream.
+    pub fn scan_exact<F: FromStr>(&mut self) -> Result<F> {
+        let value = self.inner_scan(None)?;
+        let skipped = self.peek_token()?;
+        if self.byte_buffer.len() > skipped {
+            return Err(Error::Unexpected {
+                found: String::from_utf8_lossy(&self.byte_buffer[skipped..]).into_owned(),
+                expected: "the end of the input".to_string(),
+            });
+        }
+        Ok(value)
+    }
+
+    /// Skip leading whitespace and scan a single `char`, consuming only that character.
+    ///
+    /// Unlike `scan::<char>()`, the character does not have to be followed by whitespace, so
+    /// `ab` is scanned as `'a'` followed by `'b'`. Reaching the end of the input first is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_char(&mut self) -> Result<char> {
+        act_while(&mut self.reader, |&&c| is_whitespace(c), |_| Ok(()))?;
+        expect_char(&mut self.reader)
+    }
+
+    /// Skip leading whitespace and scan a Rust char literal such as `'a'`, `'\n'` or
+    /// `'\u{1F600}'`, returning the char it stands for.
+    ///
+    /// The escapes are those of Rust: `\n`, `\r`, `\t`, `\0`, `\\`, `\'`, `\"`, `\x`
+    /// followed by two hex digits up to `7F`, and `\u{...}` with up to six hex digits. As the
+    /// literal is read char by char, `' '` is a space rather than two tokens. A missing quote,
+    /// an empty literal or an invalid escape is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected).
+    pub fn scan_char_literal(&mut self) -> Result<char> {
+        act_while(&mut self.reader, |&&c| is_whitespace(c), |_| Ok(()))?;
+        let quote = |found: char, expected: &str| Error::Unexpected {
+            found: found.to_string(),
+            expected: expected.to_string(),
+        };
+        match expect_char(&mut self.reader)? {
+            '\'' => {}
+            c => return Err(quote(c, "a `'`")),
+        }
+        let c = match expect_char(&mut self.reader)? {
+            '\\' => read_escape(&mut self.reader)?,
+            '\'' => return Err(quote('\'', "a char")),
+            c => c,
+        };
+        match expect_char(&mut self.reader)? {
+            '\'' => Ok(c),
+            found => Err(quote(found, "a closing `'`")),
+        }
+    }
+
+    /// Skip leading whitespace and return the next `char` without consuming it, or `None` at the
+    /// end of the input.
+    ///
+    /// Invalid UTF-8 is an error, but is left in the stream as well.
+    pub fn peek_char(&mut self) -> Result<Option<char>, Error<Infallible>> {
+        act_while(&mut self.reader, |&&c| is_whitespace(c), |_| Ok(()))?;
+        let (bytes, read) = read_char_bytes(&mut self.reader)?;
+        self.reader.unread(&bytes[..read]);
+        decode_char(&bytes[..read])
+    }
+
+    /// Hand `c` back to the stream, to be read again before anything else, for hand-rolled
+    /// character parsers that read one char too many.
+    ///
+    /// The char is handed back as its UTF-8 bytes, so every method sees it, from
+    /// [`scan_char`](#method.scan_char) to `scan`, and
+    /// [`bytes_consumed`](#method.bytes_consumed) goes back by its length, staying at `0` until
+    /// the char is read again when it is pushed back at the start of the input.
+    ///
+    /// # Panics
+    ///
+    /// There is room for a single char, so this panics if the previous char pushed back has not
+    /// been read again yet.
+    pub fn push_back_char(&mut self, c: char) {
+        assert!(
+            !self.reader.push_back_pending(),
+            "push_back_char: the previous char pushed back has not been read yet"
+        );
+        self.reader.push_back(c.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+
+    /// Skip leading whitespace and scan a single ASCII digit, returning its value from `0` to `9`
+    /// and consuming only that byte.
+    ///
+    /// This reads inputs like a grid of single digit cells, where `12345` holds five values. Any
+    /// other character is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected) and is
+    /// left in the stream, while reaching the end of the input first is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_digit(&mut self) -> Result<u8> {
+        act_while(&mut self.reader, |&&c| is_whitespace(c), |_| Ok(()))?;
+        match peek_byte(&mut self.reader)? {
+            Some(c) if c.is_ascii_digit() => {
+                self.reader.consume(1);
+                Ok(c - b'0')
+            }
+            Some(c) => Err(Error::Unexpected {
+                found: String::from_utf8_lossy(&[c]).into_owned(),
+                expected: "a digit".to_string(),
+            }),
+            None => Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+        }
+    }
+
+    /// Skip leading whitespace and scan the run of characters for which `pred` returns `true`,
+    /// such as all the alphanumerics of an identifier in a hand-written lexer.
+    ///
+    /// The first character that doesn't match is left in the stream, so the run may be empty.
+    /// Like [`scan_until_char_pred`](#method.scan_until_char_pred), characters that straddle a
+    /// buffer refill are reassembled before `pred` sees them.
+    pub fn scan_char_run<P: Fn(char) -> bool>(&mut self, pred: P) -> Result<String> {
+        act_while(&mut self.reader, |&&c| is_whitespace(c), |_| Ok(()))?;
+        self.scan_until_char_pred(|c| !pred(c))
+    }
+
+    /// Scan characters, whitespace included, up to the first one for which `stop` returns `true`,
+    /// which is left in the stream, or up to the end of the input.
+    ///
+    /// This reads things like everything up to the next `)`. Characters are decoded one at a
+    /// time like in [`chars`](#method.chars), so one that straddles a buffer refill is
+    /// reassembled before `stop` sees it, and a stopping character is handed back whole.
+    pub fn scan_until_char_pred<P: Fn(char) -> bool>(&mut self, stop: P) -> Result<String> {
+        let mut text = String::new();
+        while let Some(c) = read_char(&mut self.reader)? {
+            if stop(c) {
+                self.reader.unread(c.encode_utf8(&mut [0; 4]).as_bytes());
+                break;
+            }
+            text.push(c);
+        }
+        Ok(text)
+    }
+
+    /// Skip leading whitespace and scan an integer from exactly `digits` bytes, which must all be
+    /// ASCII digits, for fixed width fields such as the year, month and day of `20240531`.
+    ///
+    /// Reading stops after `digits` bytes even if more digits follow. All of them are consumed
+    /// even when one is not a digit, which is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected), while running into the end of
+    /// the input first is an [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html)
+    /// I/O error.
+    pub fn scan_exact_width_int(&mut self, digits: usize) -> Result<u64> {
+        act_while(&mut self.reader, |&&c| is_whitespace(c), |_| Ok(()))?;
+        self.byte_buffer.clear();
+        while self.byte_buffer.len() < digits {
+            let buf = self.reader.fill_buf_retrying()?;
+            if buf.is_empty() {
+                return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+            let amount = buf.len().min(digits - self.byte_buffer.len());
+            self.byte_buffer.extend_from_slice(&buf[..amount]);
+            self.reader.consume(amount);
+        }
+
+        if !self.byte_buffer.iter().all(u8::is_ascii_digit) {
+            return Err(self.unexpected(&format!("{} digits", digits)));
+        }
+        self.parse_token()
+    }
+
+    /// Skip leading whitespace and scan a string enclosed in double quotes, such as
+    /// `"New York"`, which may hold whitespace and span lines.
+    ///
+    /// A backslash escapes the character after it, so `\"` stands for a quote and `\\` for a
+    /// backslash. The quotes are consumed and not part of the string. A next character that is
+    /// not a quote is an [`Error::Unexpected`](enum.Error.html#variant.Unexpected) and is left in
+    /// the stream, while reaching the end of the input before the closing quote is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_quoted(&mut self) -> Result<String> {
+        act_while(&mut self.reader, |&&c| is_whitespace(c), |_| Ok(()))?;
+        match peek_byte(&mut self.reader)? {
+            Some(b'"') => self.reader.consume(1),
+            Some(c) => {
+                return Err(Error::Unexpected {
+                    found: String::from_utf8_lossy(&[c]).into_owned(),
+                    expected: "a `\"`".to_string(),
+                })
+            }
+            None => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+        }
+
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ..
+        } = self;
+        byte_buffer.clear();
+        loop {
+            act_while(
+                reader,
+                |&&c| c != b'"' && c != b'\\',
+                |slice| {
+                    byte_buffer.extend_from_slice(slice);
+                    Ok(())
+                },
+            )?;
+            match peek_byte(reader)? {
+                Some(b'"') => {
+                    reader.consume(1);
+                    break;
+                }
+                Some(_) => {
+                    reader.consume(1);
+                    match peek_byte(reader)? {
+                        Some(c) => {
+                            byte_buffer.push(c);
+                            reader.consume(1);
+                        }
+                        None => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                    }
+                }
+                None => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+            }
+        }
+        Ok(str::from_utf8(byte_buffer)?.to_string())
+    }
+
+    /// Scan a quoted string like [`scan_quoted`](#method.scan_quoted), collapsing every run of
+    /// whitespace inside it into a single space and trimming both of its ends, so
+    /// `" Ana \t Maria\n"` gives `Ana Maria`.
+    ///
+    /// This suits human entered names and descriptions. Use `scan_quoted` when the exact
+    /// contents matter.
+    pub fn scan_quoted_normalized(&mut self) -> Result<String> {
+        let quoted = self.scan_quoted()?;
+        Ok(quoted.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Returns an iterator over every character left in the underlying buffered reader,
+    /// whitespace included.
+    ///
+    /// Characters that straddle the buffer boundaries of the reader are reassembled. Call
+    /// [`skip_whitespace`](struct.CharsIter.html#method.skip_whitespace) on the iterator to only
+    /// get the significant characters.
+    pub fn chars(&mut self) -> CharsIter<'_, T> {
+        CharsIter {
+            stream: self,
+            skip_whitespace: false,
+        }
+    }
+
+    /// Returns an iterator over every character left in the underlying buffered reader, like
+    /// [`chars`](#method.chars), but replacing invalid UTF-8 with `U+FFFD` instead of failing.
+    ///
+    /// Invalid sequences are replaced like in
+    /// [`String::from_utf8_lossy`](https://doc.rust-lang.org/std/string/struct.String.html#method.from_utf8_lossy),
+    /// no matter how they straddle buffer refills. The iterator never yields an error: it ends at
+    /// the end of the input or at the first I/O error.
+    pub fn chars_lossy(&mut self) -> impl Iterator<Item = char> + '_ {
+        std::iter::from_fn(move || read_char_lossy(&mut self.reader).ok().flatten()).fuse()
+    }
+
+    /// Returns the current window of buffered data, filling it from the underlying reader if it
+    /// is empty, without consuming anything.
+    ///
+    /// This is the same as [`fill_buf`](https://doc.rust-lang.org/std/io/trait.BufRead.html#tymethod.fill_buf),
+    /// so the slice may be shorter than the remaining input. Use
+    /// [`consume`](https://doc.rust-lang.org/std/io/trait.BufRead.html#tymethod.consume) to
+    /// advance past bytes handled by hand.
+    pub fn peek_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf_retrying()
+    }
+
+    /// Consume and discard everything left in the underlying buffered reader, returning how many
+    /// bytes were discarded.
+    pub fn drain(&mut self) -> io::Result<u64> {
+        let mut discarded = 0;
+        loop {
+            let amount = self.reader.fill_buf_retrying()?.len();
+            if amount == 0 {
+                return Ok(discarded);
+            }
+            self.reader.consume(amount);
+            discarded += amount as u64;
+        }
+    }
+
+    /// Read everything left in the underlying buffered reader into a vector of raw bytes, like
+    /// [`read_to_end`](https://doc.rust-lang.org/std/io/trait.Read.html#method.read_to_end).
+    ///
+    /// Bytes handed back to the stream, such as peeked tokens, come first, so a text header can
+    /// be scanned before passing the binary rest through. Interrupted reads are retried like
+    /// everywhere else in the stream.
+    pub fn scan_all_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            let buf = self.reader.fill_buf_retrying()?;
+            if buf.is_empty() {
+                return Ok(bytes);
+            }
+            bytes.extend_from_slice(buf);
+            let amount = buf.len();
+            self.reader.consume(amount);
+        }
+    }
+
+    /// Scan everything left in the stream as a single string, whitespace included, so the bytes
+    /// are preserved exactly.
+    pub fn scan_remaining(&mut self) -> Result<String> {
+        String::from_utf8(self.scan_all_bytes()?).map_err(|err| Error::Utf8(err.utf8_error()))
+    }
+
+    /// Like [`scan_remaining`](#method.scan_remaining), but a single trailing `\n` or `\r\n` is
+    /// stripped, so reading the body of a file as text doesn't pick up its final line
+    /// terminator.
+    pub fn scan_remaining_trimmed(&mut self) -> Result<String> {
+        let mut remaining = self.scan_remaining()?;
+        if remaining.ends_with('\n') {
+            let _ = remaining.pop();
+            if remaining.ends_with('\r') {
+                let _ = remaining.pop();
+            }
+        }
+        Ok(remaining)
+    }
+
+    /// Fills `byte_buffer` with the leading whitespace followed by the next token, then hands all
+    /// of it back to the reader. Returns how many whitespace bytes precede the token.
+    fn peek_token(&mut self) -> io::Result<usize> {
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ref delimiters,
+            ..
+        } = self;
+        byte_buffer.clear();
+        let mut record = |slice: &[u8]| -> Result<(), Error<()>> {
+            byte_buffer.extend_from_slice(slice);
+            Ok(())
+        };
+        let result = act_while(reader, |&&c| delimiters.contains(c), &mut record)
+            .and_then(|_| act_while(reader, |&&c| !delimiters.contains(c), &mut record));
+        let skipped = byte_buffer
+            .iter()
+            .take_while(|&&c| delimiters.contains(c))
+            .count();
+        reader.unread(byte_buffer);
+        match result {
+            Err(Error::Io(err)) => Err(err),
+            _ => Ok(skipped),
+        }
+    }
+
+    /// Scan the next block of lines, up to the following blank line or the end of the input,
+    /// and hand its contents to a [`Scannable`](trait.Scannable.html).
+    ///
+    /// Blank lines before the block are skipped, so consecutive calls read consecutive blocks.
+    /// A line is blank if it only contains whitespace. The block is scanned with the delimiters
+    /// and empty field options of this stream.
+    pub fn scan_block<F: Scannable>(&mut self) -> Result<F, Error<F::Err>> {
+        let mut block = Vec::new();
+        while self.read_line(None)? {
+            if self.byte_buffer.iter().all(|&c| is_whitespace(c)) {
+                if block.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            block.extend_from_slice(&self.byte_buffer);
+            block.push(b'\n');
+        }
+
+        let mut inner = InputStream {
+            delimiters: self.delimiters.clone(),
+            keep_empty_fields: self.keep_empty_fields,
+            skip_trailing_delimiter: self.skip_trailing_delimiter,
+            ..InputStream::new(block.as_slice())
+        };
+        F::scan_from(&mut inner)
+    }
+
+    /// Scan the rest of the current line, without its `\n` or `\r\n` terminator, which is
+    /// consumed.
+    ///
+    /// Reaching the end of the input before anything could be read is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_line(&mut self) -> Result<String> {
+        self.inner_scan_line(None)
+    }
+
+    /// Read the rest of the current line like [`scan_line`](#method.scan_line) and return an
+    /// iterator over its whitespace separated tokens, without allocating any of them.
+    ///
+    /// The tokens borrow the stream, which can't be used again until the iterator is dropped.
+    /// Reaching the end of the input before anything could be read is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn line_tokens(&mut self) -> Result<LineTokens<'_>, Error<Infallible>> {
+        if !self.read_line(None)? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(split_tokens(str::from_utf8(&self.byte_buffer)?))
+    }
+
+    /// Scan the rest of the current line like [`scan_line`](#method.scan_line), failing with
+    /// [`Error::BufferLimitExceeded`](enum.Error.html#variant.BufferLimitExceeded) if it is
+    /// longer than `limit` bytes, not counting the terminator.
+    ///
+    /// An overlong line is still consumed up to and including its terminator, so the stream
+    /// stays usable from the next line on.
+    pub fn scan_line_with_limit(&mut self, limit: usize) -> Result<String> {
+        self.inner_scan_line(Some(limit))
+    }
+
+    /// Consume the rest of the current line, including its terminator.
+    pub fn skip_line(&mut self) -> io::Result<()> {
+        act_while(&mut self.reader, |&&c| c != b'\n', |_| Ok(())).map_err(into_io_error::<()>)?;
+        if peek_byte(&mut self.reader)? == Some(b'\n') {
+            self.reader.consume(1);
+        }
+        Ok(())
+    }
+
+    /// Consume the header row of a table, such as the column names of a CSV or TSV file,
+    /// leaving the stream at the start of the first data row.
+    ///
+    /// This is [`skip_line`](#method.skip_line) under a name that states the intent, so it is
+    /// meant to be called before anything else is read. An empty input is left as it is.
+    pub fn skip_header(&mut self) -> io::Result<()> {
+        self.skip_line()
+    }
+
+    /// Guess the field delimiter of the input from its first line, without consuming anything.
+    ///
+    /// The candidates are `,`, `\t`, `;` and ` `, and the one appearing most often on the line
+    /// wins, earlier candidates winning ties. Returns `None` if the line is empty or holds none
+    /// of them. The line is read whole and handed back to the stream, so the stream can then
+    /// be set up with the delimiter, say through an
+    /// [`InputStreamBuilder`](struct.InputStreamBuilder.html).
+    pub fn sniff_delimiter(&mut self) -> io::Result<Option<u8>> {
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ..
+        } = self;
+        byte_buffer.clear();
+        let result: Result<(), Error<()>> = act_while(
+            reader,
+            |&&c| c != b'\n',
+            |slice| {
+                byte_buffer.extend_from_slice(slice);
+                Ok(())
+            },
+        );
+        reader.unread(byte_buffer);
+        if let Err(Error::Io(err)) = result {
+            return Err(err);
+        }
+
+        let mut best = None;
+        let mut best_count = 0;
+        for &candidate in b",\t; " {
+            let count = byte_buffer.iter().filter(|&&c| c == candidate).count();
+            if count > best_count {
+                best = Some(candidate);
+                best_count = count;
+            }
+        }
+        Ok(best)
+    }
+
+    /// Scan a field that ends at the next `sep` byte or at the end of the line, returning
+    /// `default` if the field is empty.
+    ///
+    /// The separator after the field is consumed but the line terminator is not, so once a line
+    /// runs out of fields every further call returns `default`, which is convenient for trailing
+    /// optional columns. Use [`skip_line`](#method.skip_line) to move on to the next line. Fields
+    /// are trimmed of whitespace, and reaching the end of the input before the field is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_field_or<F: FromStr>(&mut self, sep: u8, default: F) -> Result<F> {
+        if peek_byte(&mut self.reader)?.is_none() {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ..
+        } = self;
+        byte_buffer.clear();
+        act_while(
+            reader,
+            |&&c| c != sep && c != b'\n',
+            |slice| {
+                byte_buffer.extend_from_slice(slice);
+                Ok(())
+            },
+        )?;
+        if peek_byte(reader)? == Some(sep) {
+            reader.consume(1);
+        }
+
+        let field = str::from_utf8(byte_buffer)?.trim();
+        if field.is_empty() {
+            return Ok(default);
+        }
+        field.parse().map_err(Error::FromStr)
+    }
+
+    /// Returns an iterator over the remaining lines, like [`scan_line`](#method.scan_line) until
+    /// the end of the input.
+    pub fn lines(&mut self) -> LinesIter<'_, T> {
+        LinesIter { stream: self }
+    }
+
+    /// Returns an iterator over the remaining lines like [`lines`](#method.lines), along with
+    /// their 1-based line numbers, counted from where the iterator started.
+    pub fn enumerated_lines(&mut self) -> impl Iterator<Item = (usize, Result<String>)> + '_ {
+        self.lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line))
+    }
+
+    #[inline(always)]
+    fn inner_scan_line(&mut self, limit: Option<usize>) -> Result<String> {
+        if !self.read_line(limit)? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(str::from_utf8(&self.byte_buffer)?.to_string())
+    }
+
+    /// Scan a line of fixed width fields, where `widths` holds the width in bytes of every field.
+    /// Each field is trimmed of whitespace and parsed as a type that implements
+    /// [`std::str::FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html).
+    ///
+    /// A line shorter than the sum of `widths` is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected), while anything past the last
+    /// field is ignored. The whole line is consumed in either case.
+    pub fn scan_fixed_row<F: FromStr>(
+        &mut self,
+        widths: &[usize],
+    ) -> Result<Vec<F>, Error<F::Err>> {
+        if !self.read_line(None)? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let total: usize = widths.iter().sum();
+        if self.byte_buffer.len() < total {
+            return Err(self.unexpected(&format!("a line of at least {} bytes", total)));
+        }
+
+        let mut start = 0;
+        widths
+            .iter()
+            .map(|&width| {
+                let field = &self.byte_buffer[start..start + width];
+                start += width;
+                str::from_utf8(field)?
+                    .trim()
+                    .parse()
+                    .map_err(Error::FromStr)
+            })
+            .collect()
+    }
+
+    /// Scan a single line from the underlying buffered reader and parse every whitespace
+    /// separated value on it, requiring exactly `n` of them.
+    ///
+    /// Returns [`Error::WrongCount`](enum.Error.html#variant.WrongCount) if the line holds
+    /// fewer or more than `n` values. The line terminator is consumed in either case. Reaching
+    /// the end of the input before the line is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error.
+    pub fn scan_exact_line<F: FromStr>(&mut self, n: usize) -> Result<Vec<F>, Error<F::Err>> {
+        if !self.read_line(None)? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let line = str::from_utf8(&self.byte_buffer)?;
+        let found = split_tokens(line).count();
+        if found != n {
+            return Err(Error::WrongCount { expected: n, found });
+        }
+
+        split_tokens(line)
+            .map(|token| token.parse().map_err(Error::FromStr))
+            .collect()
+    }
+
+    /// Scan a single line of booleans, such as a row of an adjacency matrix, reading every
+    /// whitespace separated value with the lenient rules of [`scan_bool`](#method.scan_bool).
+    ///
+    /// A token that is not a boolean is an
+    /// [`Error::Unexpected`](enum.Error.html#variant.Unexpected) naming it, and reaching the end
+    /// of the input before the line is an
+    /// [`UnexpectedEof`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html) I/O error. The
+    /// line terminator is consumed in either case.
+    pub fn scan_bool_row(&mut self) -> Result<Vec<bool>, Error<str::ParseBoolError>> {
+        if !self.read_line(None)? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        split_tokens(str::from_utf8(&self.byte_buffer)?)
+            .map(|token| {
+                parse_lenient_bool(token.as_bytes()).ok_or_else(|| Error::Unexpected {
+                    found: token.to_string(),
+                    expected: "a boolean".to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Fills `byte_buffer` with the next line, without its `\n` or `\r\n` terminator. Returns
+    /// `false` if the reader was already exhausted.
+    ///
+    /// A line longer than `limit` is consumed whole before failing with `BufferLimitExceeded`.
+    #[inline(always)]
+    fn read_line<E>(&mut self, limit: Option<usize>) -> Result<bool, Error<E>> {
+        let &mut InputStream {
+            ref mut reader,
+            ref mut byte_buffer,
+            ref mut after_token,
+            ..
+        } = self;
+        byte_buffer.clear();
+        *after_token = false;
+        // One more byte than the limit is kept, as it may be the `\r` of a `\r\n` terminator.
+        let kept = limit.map_or(usize::MAX, |limit| limit.saturating_add(1));
+        let mut exceeded = false;
+        let mut read = false;
+        act_while(
+            reader,
+            |&&c| c != b'\n',
+            |slice| {
+                read |= !slice.is_empty();
+                if byte_buffer.len() + slice.len() > kept {
+                    exceeded = true;
+                } else if !exceeded {
+                    byte_buffer.extend_from_slice(slice);
+                }
+                Ok(())
+            },
+        )?;
+        if peek_byte(reader)? == Some(b'\n') {
+            reader.consume(1);
+            read = true;
+        }
+
+        if byte_buffer.last() == Some(&b'\r') {
+            let _ = byte_buffer.pop();
+        }
+        if exceeded || limit.is_some_and(|limit| byte_buffer.len() > limit) {
+            byte_buffer.clear();
+            return Err(Error::BufferLimitExceeded);
+        }
+        Ok(read)
+    }
+}
+
+impl<T: BufRead + Seek> InputStream<T> {
+    /// Seeks the underlying reader back to its start and resets the state of the stream, so
+    /// scanning starts over from the top.
+    ///
+    /// Anything handed back to the stream, such as peeked tokens, is dropped and
+    /// [`bytes_consumed`](#method.bytes_consumed) goes back to `0`.
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.reader.seek_to(0)?;
+        self.reader.consumed = 0;
+        self.reader.newlines = 0;
+        self.reader.owed = 0;
+        self.reader.owed_newlines = 0;
+        self.byte_buffer.clear();
+        self.after_token = false;
+        Ok(())
+    }
+
+    /// Scan every remaining value like [`scan_all`](#method.scan_all), but first count them in
+    /// a separate pass and seek back, so the vector is allocated once with the exact capacity.
+    ///
+    /// This reads the rest of the input twice, which pays off for large inputs when seeking is
+    /// cheap, as for files. Bytes handed back to the stream, such as by
+    /// [`push_back_char`](#method.push_back_char), are kept aside and served again, as they
+    /// need not match the reader's data.
+    pub fn scan_all_presized<F: FromStr>(&mut self) -> Result<Vec<F>, Error<F::Err>> {
+        self.reader.settle();
+        let pending = self.reader.buffer[self.reader.position..].to_vec();
+        let start = self.reader.reader.stream_position()?;
+        let counts = (
+            self.reader.consumed,
+            self.reader.newlines,
+            self.reader.owed,
+            self.reader.owed_newlines,
+            self.reader.pushed_back,
+        );
+
+        let mut count = 0;
+        loop {
+            if !self.next_token()? {
+                break;
+            }
+            count += 1;
+        }
+
+        self.reader.seek_to(start)?;
+        self.reader.buffer = pending;
+        (
+            self.reader.consumed,
+            self.reader.newlines,
+            self.reader.owed,
+            self.reader.owed_newlines,
+            self.reader.pushed_back,
+        ) = counts;
+
+        let mut values = Vec::with_capacity(count);
+        for value in self.scan_iter() {
+            values.push(value?);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: BufRead> Read for InputStream<T> {
+    #[inline(always)]
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buffer)
+    }
+}
+
+impl<T: BufRead> BufRead for InputStream<T> {
+    #[inline(always)]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    #[inline(always)]
+    fn consume(&mut self, amount: usize) {
+        self.reader.consume(amount)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+    const EPS: f32 = 1e-6;
+
+    #[test]
+    fn simple_strings() {
+        let text = "Howdy neighbour, how are you doing?";
+        let mut stream = InputStream::new(text.as_bytes());
+
+        let first: String = stream.scan().expect("First string");
+        let second: String = stream.scan().expect("Second string");
+        let third: String = stream.scan().expect("Third string");
+        assert_eq!(first, "Howdy");
+        assert_eq!(second, "neighbour,");
+        assert_eq!(third, "how");
+    }
+
+    #[test]
+    fn simple_numbers() {
+        let text = "5 -7 12.5 -2.85";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(5, stream.scan().expect("5"));
+        assert_eq!(-7, stream.scan().expect("-7"));
+        assert_eq!(
+            true,
+            (12.5 - stream.scan::<f32>().expect("12.5")).abs() < EPS
+        );
+        assert_eq!(
+            true,
+            (-2.85 - stream.scan::<f32>().expect("-2.85")).abs() < EPS
+        );
+    }
+
+    #[test]
+    fn newlines() {
+        let text = "12\nHello";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(12, stream.scan().expect("12"));
+        assert_eq!("Hello", stream.scan::<String>().expect("Hello"));
+    }
+
+    #[test]
+    fn test_non_utf8() {
+        let text: [u8; 1] = [255];
+        let mut stream = InputStream::new(&text[..]);
+        assert_eq!(true, stream.scan::<i32>().is_err());
+    }
+
+    #[test]
+    fn test_not_parsing() {
+        let text = "hello";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(true, stream.scan::<i32>().is_err());
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_scan_anyhow() {
+        let text = "12 x";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(12, stream.scan_anyhow::<i32>().expect("12"));
+        let err = stream.scan_anyhow::<i32>().expect_err("x is not a number");
+        assert_eq!("Could not scan a `i32`", err.to_string());
+        assert!(err.downcast_ref::<ParseIntError>().is_some());
+    }
+
+    #[test]
+    fn test_limit_buffer() {
+        let text = "25 150 -250";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(25, stream.scan_with_limit(3).expect("25"));
+        assert_eq!(150, stream.scan_with_limit(3).expect("150"));
+        assert!(stream.scan_with_limit::<i32>(3).is_err());
+    }
+
+    #[test]
+    fn test_limit_counts_sign() {
+        let text = "-25 -250 +250 7";
+        for capacity in 1..text.len() + 1 {
+            let mut stream =
+                InputStream::new(io::BufReader::with_capacity(capacity, text.as_bytes()));
+            assert_eq!(-25, stream.scan_with_limit(3).expect("-25"));
+            for _ in 0..2 {
+                assert!(matches!(
+                    stream.scan_with_limit::<i32>(3),
+                    Err(Error::BufferLimitExceeded)
+                ));
+            }
+            assert_eq!(7, stream.scan_with_limit(3).expect("7"));
+        }
+    }
+
+    #[test]
+    fn test_next_is() {
+        let text = "  42 apple";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert!(stream.next_is::<i32>().expect("peek 42"));
+        assert!(stream.next_is::<String>().expect("peek 42 as string"));
+        assert_eq!(42, stream.scan().expect("42"));
+        assert!(!stream.next_is::<i32>().expect("peek apple"));
+        assert_eq!("apple", stream.scan::<String>().expect("apple"));
+        assert!(!stream.next_is::<String>().expect("peek at end"));
+    }
+
+    #[test]
+    fn test_peek_token_len() {
+        let text = "  abc\n\tnäive 12";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(2, text.as_bytes()));
+        for &(len, token) in &[(3, "abc"), (6, "näive"), (2, "12")] {
+            assert_eq!(len, stream.peek_token_len().expect("no io error"));
+            assert_eq!(len, stream.peek_token_len().expect("no io error"));
+            assert_eq!(token, stream.scan::<String>().expect("the token"));
+        }
+        assert_eq!(0, stream.peek_token_len().expect("no io error"));
+    }
+
+    #[test]
+    fn test_next_token_on_new_line() {
+        let text = "1 2\n3\n\n  4 5";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(2, text.as_bytes()));
+        let mut rows = vec![vec![]];
+        loop {
+            let value: i32 = stream.scan().expect("a number");
+            rows.last_mut().unwrap().push(value);
+            if stream.next_token_on_new_line().expect("no io error") {
+                rows.push(vec![]);
+            } else if !stream.next_is::<i32>().expect("no io error") {
+                break;
+            }
+        }
+        assert_eq!(vec![vec![1, 2], vec![3], vec![4, 5]], rows);
+        assert!(!stream.next_token_on_new_line().expect("no io error"));
+    }
+
+    #[test]
+    fn test_scan_char() {
+        let text = " ab\n ă€😀";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(1, text.as_bytes()));
+        assert_eq!('a', stream.scan_char().expect("a"));
+        assert_eq!('b', stream.scan_char().expect("b"));
+        assert_eq!('ă', stream.scan_char().expect("ă"));
+        assert_eq!('€', stream.scan_char().expect("€"));
+        assert_eq!('😀', stream.scan_char().expect("😀"));
+        match stream.scan_char() {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("expected the end of the input, got {:?}", other),
+        }
+
+        let text: [u8; 3] = [0xe2, 0x82, b'x'];
+        let mut stream = InputStream::new(&text[..]);
+        assert!(stream.scan_char().is_err());
+        assert_eq!('x', stream.scan_char().expect("x"));
+    }
+
+    #[test]
+    fn test_scan_char_literal() {
+        let text = r"'a' '\n'' ' '\u{1F600}' '\x41' '\'' '\\'";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(2, text.as_bytes()));
+        for &expected in &['a', '\n', ' ', '😀', 'A', '\'', '\\'] {
+            assert_eq!(
+                expected,
+                stream.scan_char_literal().expect("a char literal")
+            );
+        }
+
+        for &(text, found) in &[
+            ("a", "a"),
+            ("''", "'"),
+            ("'ab'", "b"),
+            (r"'\q'", r"\q"),
+            (r"'\x80'", r"\x80"),
+            (r"'\u{D800}'", r"\u{D800}"),
+            (r"'\u{1234567}'", r"\u{1234567"),
+        ] {
+            match InputStream::new(text.as_bytes()).scan_char_literal() {
+                Err(Error::Unexpected { found: ref got, .. }) => assert_eq!(found, got),
+                other => panic!("expected {} to be unexpected, got {:?}", text, other),
+            }
+        }
+        match InputStream::new("'a".as_bytes()).scan_char_literal() {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("expected an unexpected end of input, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_push_back_char() {
+        let mut stream = InputStream::new("  é1+".as_bytes());
+        assert_eq!(Some('é'), stream.peek_char().expect("a char"));
+        assert_eq!('é', stream.scan_char().expect("a char"));
+        let mut number = String::new();
+        loop {
+            let c = stream.scan_char().expect("a char");
+            if !c.is_ascii_digit() {
+                stream.push_back_char(c);
+                break;
+            }
+            number.push(c);
+        }
+        assert_eq!("1", number);
+        assert_eq!(Some('+'), stream.peek_char().expect("a char"));
+        assert_eq!(5, stream.bytes_consumed());
+        assert_eq!('+', stream.scan_char().expect("+"));
+        stream.push_back_char('-');
+        assert_eq!('-', stream.scan_char().expect("the pushed back char"));
+        assert_eq!(None, stream.peek_char().expect("the end"));
+        assert_eq!(6, stream.bytes_consumed());
+
+        let mut stream = InputStream::new("2 3".as_bytes());
+        assert_eq!('2', stream.scan_char().expect("2"));
+        stream.push_back_char('1');
+        assert_eq!(1, stream.scan::<i32>().expect("1"));
+        stream.push_back_char('ţ');
+        let rest: String = stream.chars().map(|c| c.expect("a char")).collect();
+        assert_eq!("ţ 3", rest);
+
+        let text = "1\n2";
+        let mut stream = InputStreamBuilder::new()
+            .track_lines(true)
+            .build(text.as_bytes());
+        stream.push_back_char('\n');
+        assert_eq!((0, 1), (stream.bytes_consumed(), stream.line_number()));
+        assert_eq!(Some('1'), stream.peek_char().expect("1"));
+        assert_eq!((0, 1), (stream.bytes_consumed(), stream.line_number()));
+        stream.push_back_char('x');
+        assert_eq!("x1", stream.scan::<String>().expect("x1"));
+        assert_eq!((1, 1), (stream.bytes_consumed(), stream.line_number()));
+        assert_eq!(2, stream.scan::<i32>().expect("2"));
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+        assert_eq!(2, stream.line_number());
+
+        let text: [u8; 3] = [0xe2, 0x82, b'x'];
+        let mut stream = InputStream::new(&text[..]);
+        assert!(stream.peek_char().is_err());
+        assert_eq!(0, stream.bytes_consumed());
+        assert!(stream.peek_char().is_err());
+        assert!(stream.scan_char().is_err());
+        assert_eq!(Some('x'), stream.peek_char().expect("x"));
+    }
+
+    #[test]
+    #[should_panic(expected = "has not been read yet")]
+    fn test_push_back_char_twice() {
+        let mut stream = InputStream::new("2 3".as_bytes());
+        assert_eq!('2', stream.scan_char().expect("2"));
+        stream.push_back_char('2');
+        assert_eq!(Some('2'), stream.peek_char().expect("2"));
+        stream.push_back_char('1');
+    }
+
+    #[test]
+    fn test_scan_digit() {
+        let text = "12\n 30x";
+        let mut stream = InputStream::new(text.as_bytes());
+        let digits: Vec<u8> = (0..4)
+            .map(|_| stream.scan_digit().expect("a digit"))
+            .collect();
+        assert_eq!(vec![1, 2, 3, 0], digits);
+        assert!(matches!(stream.scan_digit(), Err(Error::Unexpected { .. })));
+        assert_eq!('x', stream.scan_char().expect("x"));
+        match stream.scan_digit() {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("expected the end of the input, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_scan_char_run() {
+        let text = "  größe42+= x";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(1, text.as_bytes()));
+        assert_eq!(
+            "größe",
+            stream.scan_char_run(char::is_alphabetic).expect("größe")
+        );
+        assert_eq!(
+            "42",
+            stream.scan_char_run(|c| c.is_ascii_digit()).expect("42")
+        );
+        assert_eq!(
+            "",
+            stream
+                .scan_char_run(char::is_alphanumeric)
+                .expect("nothing")
+        );
+        assert_eq!(
+            "+=",
+            stream.scan_char_run(|c| "+-=".contains(c)).expect("+=")
+        );
+        assert_eq!("x", stream.scan_char_run(|_| true).expect("x"));
+        assert_eq!("", stream.scan_char_run(|_| true).expect("the end"));
+    }
+
+    #[test]
+    fn test_scan_until_char_pred() {
+        let text = "f(a b)→ab12";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(1, text.as_bytes()));
+        assert_eq!("f", stream.scan_until_char_pred(|c| c == '(').expect("f"));
+        assert_eq!('(', stream.scan_char().expect("("));
+        assert_eq!(
+            "a b",
+            stream.scan_until_char_pred(|c| c == ')').expect("a b")
+        );
+        assert_eq!(")", stream.scan_until_char_pred(|c| c == '→').expect(")"));
+        assert_eq!(
+            "→ab",
+            stream
+                .scan_until_char_pred(|c| c.is_ascii_digit())
+                .expect("→ab")
+        );
+        assert_eq!("12", stream.scan_until_char_pred(|_| false).expect("12"));
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+    }
+
+    #[test]
+    fn test_scan_exact_width_int() {
+        let text = "20240531\n 12a4 567";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(3, text.as_bytes()));
+        assert_eq!(2024, stream.scan_exact_width_int(4).expect("2024"));
+        assert_eq!(5, stream.scan_exact_width_int(2).expect("05"));
+        assert_eq!(31, stream.scan_exact_width_int(2).expect("31"));
+        match stream.scan_exact_width_int(4) {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("12a4", found),
+            _ => panic!("expected a non-digit to be rejected"),
+        }
+        match stream.scan_exact_width_int(4) {
+            Err(Error::Io(err)) => assert_eq!(io::ErrorKind::UnexpectedEof, err.kind()),
+            _ => panic!("expected the end of the input"),
+        }
+    }
+
+    #[test]
+    fn test_scan_quoted() {
+        let text = " \"New York\" \"say \\\"hi\\\" \\\\ \"\n\"\" \"  Ana \t Maria\n \" x \"open";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(2, text.as_bytes()));
+        assert_eq!("New York", stream.scan_quoted().expect("New York"));
+        assert_eq!(
+            "say \"hi\" \\ ",
+            stream.scan_quoted().expect("escaped quotes")
+        );
+        assert_eq!("", stream.scan_quoted().expect("empty string"));
+        assert_eq!(
+            "Ana Maria",
+            stream.scan_quoted_normalized().expect("Ana Maria")
+        );
+        assert!(matches!(
+            stream.scan_quoted(),
+            Err(Error::Unexpected { .. })
+        ));
+        assert_eq!("x", stream.scan::<String>().expect("x"));
+        match stream.scan_quoted() {
+            Err(Error::Io(err)) => assert_eq!(io::ErrorKind::UnexpectedEof, err.kind()),
+            _ => panic!("expected a missing closing quote"),
+        }
+    }
+
+    #[test]
+    fn test_chars() {
+        let text = "a ţ\n😀";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(1, text.as_bytes()));
+        let chars: Vec<char> = stream.chars().map(|c| c.expect("a char")).collect();
+        assert_eq!(vec!['a', ' ', 'ţ', '\n', '😀'], chars);
+
+        let mut stream = InputStream::new(text.as_bytes());
+        let chars: Vec<char> = stream
+            .chars()
+            .skip_whitespace()
+            .map(|c| c.expect("a char"))
+            .collect();
+        assert_eq!(vec!['a', 'ţ', '😀'], chars);
+    }
+
+    #[test]
+    fn test_chars_lossy() {
+        let text: &[u8] = b"a\xffb\xe2\x82c\xe0\x80\xc3\xa9\xf0\x9f\x98\x80\xed\xa0\x80\xf0\x9f";
+        for &capacity in &[1, 2, 3, 64] {
+            let mut stream = InputStream::new(io::BufReader::with_capacity(capacity, text));
+            let chars: String = stream.chars_lossy().collect();
+            assert_eq!(String::from_utf8_lossy(text), chars);
+        }
+    }
+
+    #[test]
+    fn test_scan_raw() {
+        let text = "one two\nthree";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            (b"one".to_vec(), Some(b' ')),
+            stream.scan_raw().expect("one")
+        );
+        assert_eq!(
+            (b"two".to_vec(), Some(b'\n')),
+            stream.scan_raw().expect("two")
+        );
+        assert_eq!((b"three".to_vec(), None), stream.scan_raw().expect("three"));
+    }
+
+    #[test]
+    fn test_delimiters() {
+        let text = "1,2;;3 4";
+        let mut stream = InputStream::new(text.as_bytes());
+        let is_separator = |c| c == b',' || c == b';';
+        assert_eq!(1, stream.scan_with_delimiter(is_separator).expect("1"));
+        assert_eq!(2, stream.scan_with_delimiter(is_separator).expect("2"));
+        assert_eq!(
+            "3 4",
+            stream
+                .scan_with_delimiter::<String, _>(is_separator)
+                .expect("3 4")
+        );
+
+        let mut stream = InputStream::with_delimiter_set(text.as_bytes(), b",; ");
+        let values: Vec<i32> = (0..4).map(|_| stream.scan().expect("a value")).collect();
+        assert_eq!(vec![1, 2, 3, 4], values);
+    }
+
+    #[test]
+    fn test_scan_delimited_fast() {
+        let text = ",,alpha,12,,be ta\n,";
+        for capacity in 1..text.len() + 1 {
+            let mut stream =
+                InputStream::new(io::BufReader::with_capacity(capacity, text.as_bytes()));
+            assert_eq!(
+                "alpha",
+                stream.scan_delimited_fast::<String>(b',').expect("alpha")
+            );
+            assert_eq!(12, stream.scan_delimited_fast::<i32>(b',').expect("12"));
+            assert_eq!(
+                "be ta\n",
+                stream.scan_delimited_fast::<String>(b',').expect("be ta")
+            );
+            assert_eq!(
+                "",
+                stream.scan_delimited_fast::<String>(b',').expect("nothing")
+            );
+        }
+    }
+
+    #[test]
+    fn test_bools() {
+        let text = "true false True yes NO 1 0 maybe";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert!(stream.scan_bool_strict().expect("true"));
+        assert!(!stream.scan_bool_strict().expect("false"));
+        match stream.scan_bool_strict() {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("True", found),
+            _ => panic!("expected True to be rejected"),
+        }
+        let lenient: Vec<bool> = (0..4)
+            .map(|_| stream.scan_bool().expect("a bool"))
+            .collect();
+        assert_eq!(vec![true, false, true, false], lenient);
+        assert!(stream.scan_bool().is_err());
+    }
+
+    #[test]
+    fn test_scan_flag() {
+        let text = "0 1 -1 42 +0 000 99999999999999999999 1.0 -";
+        let mut stream = InputStream::new(text.as_bytes());
+        let flags: Vec<bool> = (0..7)
+            .map(|_| stream.scan_flag().expect("a flag"))
+            .collect();
+        assert_eq!(vec![false, true, true, true, false, false, true], flags);
+        for &found in &["1.0", "-", ""] {
+            match stream.scan_flag() {
+                Err(Error::Unexpected { found: ref got, .. }) => assert_eq!(found, got),
+                other => panic!("expected {:?} to be rejected, got {:?}", found, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan_remaining() {
+        let mut stream = InputStream::new("title\nsome body\r\n\n".as_bytes());
+        assert_eq!("title", stream.scan::<String>().expect("title"));
+        assert_eq!(
+            "\nsome body\r\n\n",
+            stream.scan_remaining().expect("the body")
+        );
+        assert_eq!("", stream.scan_remaining().expect("nothing"));
+
+        let mut stream = InputStream::new("title\nsome body\r\n".as_bytes());
+        stream.skip_line().expect("the title");
+        assert_eq!(
+            "some body",
+            stream.scan_remaining_trimmed().expect("the body")
+        );
+
+        let mut stream = InputStream::new("a\n\n".as_bytes());
+        assert_eq!("a\n", stream.scan_remaining_trimmed().expect("one newline"));
+    }
+
+    #[test]
+    fn test_line_tokens() {
+        let text = "the quick  brown\tfox\r\n\njumps";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(3, text.as_bytes()));
+        let words: Vec<&str> = stream.line_tokens().expect("a line").collect();
+        assert_eq!(vec!["the", "quick", "brown", "fox"], words);
+        assert_eq!(0, stream.line_tokens().expect("a blank line").count());
+        let longest = stream
+            .line_tokens()
+            .expect("a line")
+            .max_by_key(|word| word.len());
+        assert_eq!(Some("jumps"), longest);
+        match stream.line_tokens() {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("expected an unexpected end of input, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_scan_block() {
+        #[derive(Debug, PartialEq)]
+        struct Record {
+            id: u32,
+            scores: Vec<u32>,
+        }
+
+        impl Scannable for Record {
+            type Err = ParseIntError;
+
+            fn scan_from<T: BufRead>(
+                stream: &mut InputStream<T>,
+            ) -> Result<Self, Error<Self::Err>> {
+                let id = stream.scan()?;
+                let scores = Vec::scan_from(stream)?;
+                Ok(Record { id, scores })
+            }
+        }
+
+        let text = "\n7 1 2\n3\n  \n\n8\n4\n\n\n";
+        let mut stream = InputStream::new(text.as_bytes());
+        let first: Record = stream.scan_block().expect("first block");
+        assert_eq!(
+            Record {
+                id: 7,
+                scores: vec![1, 2, 3]
+            },
+            first
+        );
+        let second: Record = stream.scan_block().expect("second block");
+        assert_eq!(
+            Record {
+                id: 8,
+                scores: vec![4]
+            },
+            second
+        );
+        assert!(stream
+            .scan_block::<Vec<i32>>()
+            .expect("no block")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_scan_block_delimiters() {
+        let text = "1,2\n3\n\n4,5";
+        let mut stream = InputStream::with_delimiter_set(text.as_bytes(), b",\n");
+        assert_eq!(
+            vec![1, 2, 3],
+            stream.scan_block::<Vec<i32>>().expect("1 2 3")
+        );
+        assert_eq!(vec![4, 5], stream.scan_block::<Vec<i32>>().expect("4 5"));
+
+        let mut stream = InputStream::with_delimiter_set("a,,b\n".as_bytes(), b",\n");
+        stream.keep_empty_fields(true);
+        let fields = stream.scan_block::<Vec<String>>().expect("fields");
+        assert_eq!(vec!["a", "", "b"], fields);
+    }
+
+    #[test]
+    fn test_scan_percent() {
+        let text = "42% 0.5 12.5% %";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert!((0.42 - stream.scan_percent().expect("42%")).abs() < 1e-9);
+        assert!((0.5 - stream.scan_percent().expect("0.5")).abs() < 1e-9);
+        assert!((0.125 - stream.scan_percent().expect("12.5%")).abs() < 1e-9);
+        assert!(stream.scan_percent().is_err());
+    }
+
+    #[test]
+    fn test_scan_float_special() {
+        let text = "+Inf -Infinity ∞ -∞ 1.#INF -1.#inf 2.5 NaN -nan 1.#QNAN -1.#IND nan(0x7ff8) \
+                    -NAN(ind) 1.#INFO nan(";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(3, text.as_bytes()));
+        for &expected in &[
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            2.5,
+        ] {
+            assert_eq!(expected, stream.scan_float_special().expect("a float"));
+        }
+        for _ in 0..6 {
+            assert!(stream.scan_float_special().expect("a NaN").is_nan());
+        }
+        assert!(stream.scan_float_special().is_err());
+        assert!(stream.scan_float_special().is_err());
+    }
+
+    #[test]
+    fn test_scan_seconds() {
+        let text = "90 1.5 0.001 -1 NaN 1e300 inf x";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(Duration::from_secs(90), stream.scan_seconds().expect("90"));
+        assert_eq!(
+            Duration::from_millis(1500),
+            stream.scan_seconds().expect("1.5")
+        );
+        assert_eq!(
+            Duration::from_millis(1),
+            stream.scan_seconds().expect("0.001")
+        );
+        for &found in &["-1", "NaN", "1e300", "inf"] {
+            match stream.scan_seconds() {
+                Err(Error::Unexpected { found: ref got, .. }) => assert_eq!(found, got),
+                other => panic!("expected {} to be rejected, got {:?}", found, other),
+            }
+        }
+        assert!(matches!(stream.scan_seconds(), Err(Error::FromStr(_))));
+    }
+
+    #[test]
+    fn test_scan_nonzero() {
+        let text = "5 -3 0 x";
+        let mut stream = InputStream::new(text.as_bytes());
+        let five: num::NonZeroU32 = stream.scan_nonzero().expect("5");
+        assert_eq!(5, five.get());
+        let minus_three: num::NonZeroI64 = stream.scan_nonzero().expect("-3");
+        assert_eq!(-3, minus_three.get());
+        assert!(matches!(
+            stream.scan_nonzero::<num::NonZeroU8>(),
+            Err(Error::Zero)
+        ));
+        assert!(matches!(
+            stream.scan_nonzero::<num::NonZeroU8>(),
+            Err(Error::FromStr(_))
+        ));
+    }
+
+    #[test]
+    fn test_interrupt_retries() {
+        struct Interrupting<'a> {
+            interruptions: usize,
+            data: &'a [u8],
+        }
+
+        impl<'a> Read for Interrupting<'a> {
+            fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+                if self.interruptions > 0 {
+                    self.interruptions -= 1;
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+                self.data.read(buffer)
+            }
+        }
+
+        let reader = Interrupting {
+            interruptions: 3,
+            data: b"12",
+        };
+        let mut stream = InputStream::new(io::BufReader::new(reader));
+        assert_eq!(12, stream.scan().expect("12"));
+
+        let reader = Interrupting {
+            interruptions: 3,
+            data: b"12",
+        };
+        let mut stream = InputStream::new(io::BufReader::new(reader));
+        stream.set_interrupt_retries(Some(2));
+        match stream.scan::<i32>() {
+            Err(Error::Io(err)) => assert_eq!(io::ErrorKind::Interrupted, err.kind()),
+            _ => panic!("expected the interruption to be returned"),
+        }
+        assert_eq!(12, stream.scan().expect("12"));
+    }
+
+    #[test]
+    fn test_max_empty_fills() {
+        struct Stalling<'a> {
+            chunks: Vec<&'a [u8]>,
+        }
+
+        impl<'a> Read for Stalling<'a> {
+            fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+                match self.chunks.pop() {
+                    Some(mut chunk) => chunk.read(buffer),
+                    None => Ok(0),
+                }
+            }
+        }
+
+        let stalling = || Stalling {
+            chunks: vec![b"2 ", b"", b"", b"1 "],
+        };
+        let mut stream = InputStream::new(io::BufReader::new(stalling()));
+        assert_eq!(1, stream.scan().expect("1"));
+        assert_eq!("", stream.scan::<String>().expect("an early end"));
+
+        let mut stream = InputStreamBuilder::new()
+            .max_empty_fills(3)
+            .build(io::BufReader::new(stalling()));
+        assert_eq!(vec![1, 2], stream.scan_n::<i32>(2).expect("1 2"));
+        match stream.scan::<i32>() {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::TimedOut => {}
+            other => panic!("expected the stalled reader to time out, got {:?}", other),
+        }
+
+        let mut stream = InputStream::new(io::BufReader::new(stalling()));
+        stream.set_max_empty_fills(2);
+        assert_eq!(1, stream.scan().expect("1"));
+        match stream.scan::<i32>() {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::TimedOut => {}
+            other => panic!("expected two empty reads to time out, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_scan_n_and_slice() {
+        let text = "1 2 3 4 5 6";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(vec![1, 2], stream.scan_n::<i32>(2).expect("1 2"));
+        let mut out = [0; 3];
+        stream.scan_slice(&mut out).expect("3 4 5");
+        assert_eq!([3, 4, 5], out);
+        match stream.scan_slice(&mut out) {
+            Err(Error::Io(err)) => assert_eq!(io::ErrorKind::UnexpectedEof, err.kind()),
+            _ => panic!("expected the end of the input"),
+        }
+        assert_eq!([6, 4, 5], out);
+    }
+
+    #[test]
+    fn test_scan_vec_until_blank() {
+        let text = "\n1 2\n3\r\n \t\r\n4\n\n\n5 6";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![1, 2, 3],
+            stream.scan_vec_until_blank::<i32>().expect("1 2 3")
+        );
+        assert_eq!(vec![4], stream.scan_vec_until_blank::<i32>().expect("4"));
+        assert_eq!(
+            vec![5, 6],
+            stream.scan_vec_until_blank::<i32>().expect("5 6")
+        );
+        assert!(stream
+            .scan_vec_until_blank::<i32>()
+            .expect("nothing")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_scan_iter() {
+        let text = "1 2 3\n4 ";
+        let mut stream = InputStream::new(text.as_bytes());
+        let values: Vec<i32> = stream.scan_iter().map(|v| v.expect("a value")).collect();
+        assert_eq!(vec![1, 2, 3, 4], values);
+
+        let mut stream = InputStream::new(text.as_bytes());
+        let indices: Vec<usize> = stream
+            .scan_map_iter(|index: usize| index - 1)
+            .map(|v| v.expect("an index"))
+            .collect();
+        assert_eq!(vec![0, 1, 2, 3], indices);
+
+        let mut stream = InputStream::new("5 x".as_bytes());
+        let mut doubled = stream.scan_map_iter(|value: i32| value * 2);
+        assert_eq!(10, doubled.next().expect("5").expect("5"));
+        assert!(matches!(doubled.next(), Some(Err(Error::FromStr(_)))));
+        assert!(doubled.next().is_none());
+    }
+
+    #[test]
+    fn test_scan_spanned() {
+        let text = "  12 hello\n-3";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(2, text.as_bytes()));
+        assert_eq!((12, 2..4), stream.scan_spanned().expect("12"));
+        assert!(stream.next_is::<String>().expect("peek hello"));
+        assert_eq!(4, stream.bytes_consumed());
+        let (hello, span) = stream.scan_spanned::<String>().expect("hello");
+        assert_eq!(("hello", 5..10), (hello.as_str(), span));
+        assert_eq!((-3, 11..13), stream.scan_spanned().expect("-3"));
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+    }
+
+    #[test]
+    fn test_scan_with_line() {
+        let text = "1 2\n\n  3\r\nx\n4";
+        for capacity in 1..text.len() + 1 {
+            let mut stream = InputStreamBuilder::new()
+                .track_lines(true)
+                .build(io::BufReader::with_capacity(capacity, text.as_bytes()));
+            assert_eq!((1, 1), stream.scan_with_line().expect("1"));
+            assert!(stream.next_is::<i32>().expect("peek 2"));
+            assert_eq!((2, 1), stream.scan_with_line().expect("2"));
+            assert_eq!((3, 3), stream.scan_with_line().expect("3"));
+            assert!(stream.scan_with_line::<i32>().is_err());
+            assert_eq!("4", stream.scan_cow().expect("4"));
+            assert_eq!(5, stream.line_number());
+        }
+    }
+
+    #[test]
+    fn test_scan_echo() {
+        let text = "007 +1.50 x";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!((7, "007".to_string()), stream.scan_echo().expect("007"));
+        assert_eq!(
+            (1.5, "+1.50".to_string()),
+            stream.scan_echo().expect("+1.50")
+        );
+        assert!(stream.scan_echo::<i32>().is_err());
+    }
+
+    #[test]
+    fn test_tokens_with_positions() {
+        let text = "let x\n  = 42;";
+        for capacity in 1..text.len() + 1 {
+            let mut stream =
+                InputStream::new(io::BufReader::with_capacity(capacity, text.as_bytes()));
+            let tokens: Vec<(Range<u64>, String)> = stream
+                .tokens_with_positions()
+                .map(|token| token.expect("a token"))
+                .collect();
+            let expected = vec![(0..3, "let"), (4..5, "x"), (8..9, "="), (10..13, "42;")];
+            let expected: Vec<(Range<u64>, String)> = expected
+                .into_iter()
+                .map(|(span, token)| (span, token.to_string()))
+                .collect();
+            assert_eq!(expected, tokens);
+        }
+    }
+
+    #[test]
+    fn test_scan_word() {
+        let text = "hello,world! (don't) stop...";
+        let mut stream = InputStream::new(text.as_bytes());
+        let words: Vec<String> = (0..5)
+            .map(|_| stream.scan_word().expect("a word"))
+            .collect();
+        assert_eq!(vec!["hello", "world", "don't", "stop", ""], words);
+    }
+
+    #[test]
+    fn test_case_folding() {
+        let text = "Hello ŞtraSSe";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!("hello", stream.scan_lower().expect("hello"));
+        assert_eq!("ŞTRASSE", stream.scan_upper().expect("ŞTRASSE"));
+    }
+
+    #[test]
+    fn test_scan_trimmed() {
+        let text = "[42] \"quoted\" ]]";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!("42", stream.scan_trimmed(&['[', ']']).expect("42"));
+        assert_eq!("quoted", stream.scan_trimmed(&['"']).expect("quoted"));
+        assert_eq!("", stream.scan_trimmed(&[']']).expect("nothing left"));
+    }
+
+    #[test]
+    fn test_scan_split() {
+        let text = "1,2,3 10:20:30 a::b 4,,5";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(vec![1, 2, 3], stream.scan_split::<i32>(',').expect("1,2,3"));
+        assert_eq!(
+            vec![10, 20, 30],
+            stream.scan_split::<i32>(':').expect("10:20:30")
+        );
+        assert_eq!(
+            vec!["a", "", "b"],
+            stream.scan_split::<String>(':').expect("a::b")
+        );
+        assert!(stream.scan_split::<i32>(',').is_err());
+        assert!(stream.scan_split::<i32>(',').expect("nothing").is_empty());
+    }
+
+    #[test]
+    fn test_word_counts() {
+        let text = "the cat and the hat\nthe end";
+        let mut stream = InputStream::new(text.as_bytes());
+        let counts = stream.word_counts().expect("counts");
+        assert_eq!(5, counts.len());
+        assert_eq!(Some(&3), counts.get("the"));
+        assert_eq!(Some(&1), counts.get("hat"));
+
+        let text: [u8; 3] = [b'a', b' ', 255];
+        let mut stream = InputStream::new(&text[..]);
+        let err = stream.word_counts().expect_err("invalid utf8");
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn test_scan_fold() {
+        let text = "1 2 3\n4\n";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            10,
+            stream
+                .scan_fold(0, |sum, value: i64| sum + value)
+                .expect("sum")
+        );
+
+        let mut stream = InputStream::new("5 x 7".as_bytes());
+        assert!(matches!(
+            stream.scan_fold(0, |sum, value: i64| sum + value),
+            Err(Error::FromStr(_))
+        ));
+        assert_eq!(7, stream.scan::<i32>().expect("7"));
+    }
+
+    #[test]
+    fn test_tally_into() {
+        let text = "1 2 1\n3 1 x 2";
+        let mut stream = InputStream::new(text.as_bytes());
+        let mut counts = HashMap::new();
+        stream.tally_into::<i32>(&mut counts, 3).expect("1 2 1");
+        stream.tally_into::<i32>(&mut counts, 2).expect("3 1");
+        assert_eq!(3, counts.len());
+        assert_eq!(Some(&3), counts.get(&1));
+        assert_eq!(Some(&1), counts.get(&3));
+        assert!(stream.tally_into::<i32>(&mut counts, 1).is_err());
+        assert!(stream.tally_into::<i32>(&mut counts, 2).is_err());
+        assert_eq!(Some(&2), counts.get(&2));
+    }
+
+    #[test]
+    fn test_for_each_value() {
+        let mut values = Vec::new();
+        let mut stream = InputStream::new("3 1\n2\n".as_bytes());
+        stream
+            .for_each_value(|value: u8| values.push(value))
+            .expect("3 1 2");
+        assert_eq!(vec![3, 1, 2], values);
+
+        let mut stream = InputStream::new("4 300 5".as_bytes());
+        assert!(matches!(
+            stream.for_each_value(|value: u8| values.push(value)),
+            Err(Error::FromStr(_))
+        ));
+        assert_eq!(vec![3, 1, 2, 4], values);
+    }
+
+    #[test]
+    fn test_scan_grid_with_dims() {
+        let text = "2 3\n1 2 3\n4 5 6\n0 9\n-1 2";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![vec![1, 2, 3], vec![4, 5, 6]],
+            stream.scan_grid_with_dims::<i32>().expect("2x3 grid")
+        );
+        assert!(stream
+            .scan_grid_with_dims::<i32>()
+            .expect("empty grid")
+            .is_empty());
+        match stream.scan_grid_with_dims::<i32>() {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("-1", found),
+            _ => panic!("expected a negative dimension to be rejected"),
+        }
+
+        let text = format!("{} 0 7", usize::MAX);
+        let mut stream = InputStream::new(text.as_bytes());
+        assert!(stream
+            .scan_grid_with_dims::<i32>()
+            .expect("a grid without columns")
+            .is_empty());
+        assert_eq!(7, stream.scan().expect("7"));
+
+        let text = format!("{} 2 1", usize::MAX);
+        let mut stream = InputStream::new(text.as_bytes());
+        assert!(matches!(
+            stream.scan_grid_with_dims::<i32>(),
+            Err(Error::Unexpected { .. })
+        ));
+    }
+
+    #[test]
+    fn test_scan_rle() {
+        let text = "3 7 0 1 2 -1\n1 5 2 x";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![7, 7, 7, -1, -1],
+            stream.scan_rle::<i32>(3, Some(5)).expect("three runs")
+        );
+        assert_eq!(vec![5], stream.scan_rle::<i32>(1, None).expect("a run"));
+        assert!(stream.scan_rle::<i32>(1, None).is_err());
+
+        let mut stream = InputStream::new("2 1 18446744073709551615 2".as_bytes());
+        match stream.scan_rle::<i32>(2, Some(1000)) {
+            Err(Error::BufferLimitExceeded) => {}
+            other => panic!("expected the limit to be exceeded, got {:?}", other),
+        }
+        match InputStream::new("-3 1".as_bytes()).scan_rle::<i32>(1, None) {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("-3", found),
+            other => panic!("expected a bad count, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_scan_increasing() {
+        let text = "1 2 5 5 7 3";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![1, 2, 5],
+            stream.scan_increasing::<i32>(3).expect("1 2 5")
+        );
+        assert_eq!(
+            vec![5, 7],
+            stream.scan_nondecreasing::<i32>(2).expect("5 7")
+        );
+
+        let mut stream = InputStream::new(text.as_bytes());
+        match stream.scan_increasing::<i32>(6) {
+            Err(Error::Unexpected { found, expected }) => {
+                assert_eq!("5", found);
+                assert!(expected.ends_with("index 3"));
+            }
+            other => panic!("expected the repeated 5 to be rejected, got {:?}", other),
+        }
+        let mut stream = InputStream::new(text.as_bytes());
+        match stream.scan_nondecreasing::<f64>(6) {
+            Err(Error::Unexpected { found, expected }) => {
+                assert_eq!("3", found);
+                assert!(expected.ends_with("index 5"));
+            }
+            other => panic!("expected 3 to be rejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_scan_named_fields() {
+        let text = "y=2 x=1 label=a=b\nx=3 x=4\nx=5 z=6\nx 7\nx=8";
+        let mut stream = InputStream::new(text.as_bytes());
+        let fields = stream
+            .scan_named_fields(&["x", "y", "label"], b'=')
+            .expect("a record");
+        assert_eq!(3, fields.len());
+        assert_eq!(("1", "2"), (&fields["x"][..], &fields["y"][..]));
+        assert_eq!("a=b", fields["label"]);
+
+        for &message in &[
+            "Expected each of x, y at most once, found `x`",
+            "Expected a field named one of x, y, found `z`",
+            "Expected a `key=value` field, found `x`",
+        ] {
+            match stream.scan_named_fields(&["x", "y"], b'=') {
+                Err(err @ Error::Unexpected { .. }) => assert_eq!(message, err.to_string()),
+                other => panic!("expected {:?}, got {:?}", message, other),
+            }
+        }
+        let _ = stream.scan::<String>().expect("7");
+        match stream.scan_named_fields(&["x", "y", "z"], b'=') {
+            Err(Error::MissingFields(missing)) => assert_eq!(vec!["y", "z"], missing),
+            other => panic!("expected missing fields, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_scan_unique() {
+        let text = "3 1 3 2 1 03\n5";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![3, 1, 2, 5],
+            stream.scan_unique::<i32>().expect("values")
+        );
+        assert!(stream.scan_unique::<i32>().expect("nothing").is_empty());
+
+        let mut stream = InputStream::new("b a b x".as_bytes());
+        assert_eq!(
+            vec!["b", "a", "x"],
+            stream.scan_unique::<String>().expect("words")
+        );
+        assert!(InputStream::new("1 x".as_bytes())
+            .scan_unique::<i32>()
+            .is_err());
+
+        // Only the first of the equal values is kept, without cloning it.
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Word(String);
+
+        impl FromStr for Word {
+            type Err = Infallible;
+
+            fn from_str(text: &str) -> Result<Self, Infallible> {
+                Ok(Word(text.to_lowercase()))
+            }
+        }
+
+        let mut stream = InputStream::new("Ab x aB y".as_bytes());
+        assert_eq!(
+            vec![Word("ab".into()), Word("x".into()), Word("y".into())],
+            stream.scan_unique::<Word>().expect("words")
+        );
+    }
+
+    #[test]
+    fn test_scan_collect() {
+        use std::collections::{BTreeSet, VecDeque};
+
+        let text = "3 1 3 2 4 5 x";
+        let mut stream = InputStream::new(text.as_bytes());
+        let set: BTreeSet<i32> = stream.scan_collect(4).expect("3 1 3 2");
+        assert_eq!(vec![1, 2, 3], set.into_iter().collect::<Vec<_>>());
+        let deque: VecDeque<i32> = stream.scan_collect(2).expect("4 5");
+        assert_eq!(VecDeque::from(vec![4, 5]), deque);
+        assert!(stream.scan_collect::<i32, Vec<_>>(1).is_err());
+    }
+
+    #[test]
+    fn test_scan_sorted() {
+        let text = "3 1 3 2 5 5 4 x";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![1, 2, 3, 3],
+            stream.scan_sorted::<i32>(4, false).expect("3 1 3 2")
+        );
+        assert_eq!(
+            vec![4, 5],
+            stream.scan_sorted::<i32>(3, true).expect("5 5 4")
+        );
+        assert!(stream.scan_sorted::<i32>(1, true).is_err());
+    }
+
+    #[test]
+    fn test_scan_bool_row() {
+        let text = "1 0 yes\r\n\nTrue maybe 0\n";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![true, false, true],
+            stream.scan_bool_row().expect("1 0 yes")
+        );
+        assert!(stream.scan_bool_row().expect("empty row").is_empty());
+        match stream.scan_bool_row() {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("maybe", found),
+            _ => panic!("expected maybe to be rejected"),
+        }
+        assert!(stream.scan_bool_row().is_err());
+    }
+
+    #[test]
+    fn test_crlf() {
+        let unix = "1 2\n3 4\n\n5 6\n";
+        let dos = "1 2\r\n3 4\r\n\r\n5 6\r\n";
+
+        let tokens = |text: &str| -> Vec<String> {
+            let mut stream = InputStream::new(text.as_bytes());
+            stream.tokens().map(|t| t.expect("a token")).collect()
+        };
+        assert_eq!(tokens(unix), tokens(dos));
+
+        let lines = |text: &str| {
+            let mut stream = InputStream::new(text.as_bytes());
+            let first = stream.scan_exact_line::<i32>(2).expect("first line");
+            let second = stream.scan_exact_line::<i32>(2).expect("second line");
+            let blank = stream.scan_exact_line::<i32>(0).expect("blank line");
+            (first, second, blank)
+        };
+        assert_eq!(lines(unix), lines(dos));
+
+        let blocks = |text: &str| {
+            let mut stream = InputStream::new(text.as_bytes());
+            let first = stream.scan_block::<Vec<i32>>().expect("first block");
+            let until_blank = stream.scan_vec_until_blank::<i32>().expect("second block");
+            (first, until_blank)
+        };
+        assert_eq!((vec![1, 2, 3, 4], vec![5, 6]), blocks(unix));
+        assert_eq!(blocks(unix), blocks(dos));
+
+        let mut stream = InputStream::new("7 8\r".as_bytes());
+        assert_eq!(vec![7, 8], stream.scan_exact_line::<i32>(2).expect("7 8"));
+        let mut stream = InputStream::new("7 8\r".as_bytes());
+        assert_eq!(vec![7, 8], stream.scan_block::<Vec<i32>>().expect("7 8"));
+    }
+
+    #[test]
+    fn test_scan_counted() {
+        let text = "3 1 2 3 2 4 5 -1 7";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![1, 2, 3],
+            stream.scan_counted::<i32, usize>().expect("3 values")
+        );
+        assert_eq!(
+            vec![4, 5],
+            stream.scan_counted::<i32, u64>().expect("2 values")
+        );
+        match stream.scan_counted::<i32, i64>() {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("-1", found),
+            _ => panic!("expected a negative count to be rejected"),
+        }
+        assert!(stream.scan_counted::<i32, u8>().is_err());
+    }
+
+    #[test]
+    fn test_peek_buf() {
+        let text = "abc def";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(4, text.as_bytes()));
+        assert_eq!(b"abc ", stream.peek_buf().expect("a window"));
+        stream.consume(2);
+        assert_eq!("c", stream.scan::<String>().expect("c"));
+        assert_eq!(b" ", stream.peek_buf().expect("the rest of the window"));
+    }
+
+    #[test]
+    fn test_scan_uint() {
+        let text = "5 +5 -5 x +5";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(5, stream.scan_uint::<u32>().expect("5"));
+        assert!(matches!(stream.scan_uint::<u32>(), Err(Error::Signed('+'))));
+        assert!(matches!(stream.scan_uint::<u64>(), Err(Error::Signed('-'))));
+        assert!(matches!(stream.scan_uint::<u8>(), Err(Error::FromStr(_))));
+        assert_eq!(5, stream.scan::<i32>().expect("+5"));
+    }
+
+    #[test]
+    fn test_scan_line() {
+        let text = "first line\r\n\nsecond   one\nlast";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!("first line", stream.scan_line().expect("first line"));
+        assert_eq!("", stream.scan_line().expect("empty line"));
+        assert_eq!("second   one", stream.scan_line().expect("second one"));
+        assert_eq!("last", stream.scan_line().expect("last"));
+        assert!(stream.scan_line().is_err());
+    }
+
+    #[test]
+    fn test_scan_line_with_limit() {
+        let text = "short\r\nway too long\nfive!\n";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(3, text.as_bytes()));
+        assert_eq!("short", stream.scan_line_with_limit(5).expect("short"));
+        assert!(matches!(
+            stream.scan_line_with_limit(5),
+            Err(Error::BufferLimitExceeded)
+        ));
+        assert_eq!("five!", stream.scan_line_with_limit(5).expect("five!"));
+    }
+
+    #[test]
+    fn test_scan_checked_utf8() {
+        let text = b"ok ab\xffc next";
+        let mut stream = InputStream::new(&text[..]);
+        assert_eq!("ok", stream.scan_checked_utf8().expect("ok"));
+        match stream.scan_checked_utf8() {
+            Err(Error::Utf8Token { token, error }) => {
+                assert_eq!(b"ab\xffc".to_vec(), token);
+                assert_eq!(2, error.valid_up_to());
+            }
+            _ => panic!("expected invalid utf8"),
+        }
+        assert_eq!("next", stream.scan_checked_utf8().expect("next"));
+    }
+
+    #[test]
+    fn test_scan_fixed_row() {
+        let text = "2024 531  7\n123\n";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![2024, 5, 31, 7],
+            stream
+                .scan_fixed_row::<u32>(&[4, 2, 2, 3])
+                .expect("2024 5 31 7")
+        );
+        assert!(matches!(
+            stream.scan_fixed_row::<u32>(&[2, 2]),
+            Err(Error::Unexpected { .. })
+        ));
+        assert!(stream.scan_fixed_row::<u32>(&[1]).is_err());
+    }
+
+    #[test]
+    fn test_scan_all_presized() {
+        let text = "skip 1 2 3\n4 5";
+        let mut stream = InputStream::new(io::Cursor::new(text.as_bytes()));
+        assert_eq!("skip", stream.scan::<String>().expect("skip"));
+        assert!(stream.next_is::<i32>().expect("peek 1"));
+        let values = stream.scan_all_presized::<i32>().expect("all values");
+        assert_eq!(vec![1, 2, 3, 4, 5], values);
+        assert_eq!(5, values.capacity());
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+
+        let mut stream = InputStream::new("6 7".as_bytes());
+        assert_eq!(vec![6, 7], stream.scan_all::<i32>().expect("6 7"));
+
+        let mut stream = InputStream::new(io::Cursor::new("1 2 3".as_bytes()));
+        stream.push_back_char('8');
+        assert_eq!(
+            vec![81, 2, 3],
+            stream.scan_all_presized::<i32>().expect("81 2 3")
+        );
+
+        let mut stream = InputStream::new(io::Cursor::new("1 2 3".as_bytes()));
+        assert_eq!('1', stream.scan_char().expect("1"));
+        stream.push_back_char('9');
+        let values = stream.scan_all_presized::<i32>().expect("9 2 3");
+        assert_eq!(vec![9, 2, 3], values);
+        assert_eq!(3, values.capacity());
+    }
+
+    #[test]
+    fn test_scan_validated() {
+        let text = "abc12 ab_c ţară 42";
         let mut stream = InputStream::new(text.as_bytes());
-        assert_eq!(25, stream.scan_with_limit(3).expect("25"));
-        assert_eq!(150, stream.scan_with_limit(3).expect("150"));
-        assert!(stream.scan_with_limit::<i32>(3).is_err());
+        let alphanumeric = |c: char| c.is_alphanumeric();
+        assert_eq!(
+            "abc12",
+            stream
+                .scan_validated::<String, _>(alphanumeric)
+                .expect("abc12")
+        );
+        match stream.scan_validated::<String, _>(alphanumeric) {
+            Err(err @ Error::InvalidChar { .. }) => assert_eq!(
+                "Character '_' at byte 2 of the token is not allowed",
+                err.to_string()
+            ),
+            _ => panic!("expected `_` to be rejected"),
+        }
+        assert!(matches!(
+            stream.scan_validated::<String, _>(|c: char| c.is_ascii()),
+            Err(Error::InvalidChar {
+                found: 'ţ',
+                position: 0
+            })
+        ));
+        assert_eq!(
+            42,
+            stream
+                .scan_validated::<u32, _>(|c: char| c.is_ascii_digit())
+                .expect("42")
+        );
+    }
+
+    #[test]
+    fn test_scan_int_grouped() {
+        let text = "1,234,567 -12,345 999 1234 12,34 1234,567 ,123";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(1_234_567, stream.scan_int_grouped(',').expect("1,234,567"));
+        assert_eq!(-12_345, stream.scan_int_grouped(',').expect("-12,345"));
+        assert_eq!(999, stream.scan_int_grouped(',').expect("999"));
+        assert_eq!(1234, stream.scan_int_grouped(',').expect("1234"));
+        for _ in 0..3 {
+            assert!(matches!(
+                stream.scan_int_grouped(','),
+                Err(Error::Unexpected { .. })
+            ));
+        }
+
+        let mut stream = InputStream::new("12 345".as_bytes());
+        match stream.scan_int_grouped(' ') {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::InvalidInput => {}
+            other => panic!("expected a delimiter to be rejected, got {:?}", other),
+        }
+        assert_eq!(12, stream.scan_int_grouped(',').expect("12"));
+
+        let mut stream = InputStream::with_delimiter_set("-12 345;7".as_bytes(), b";\n");
+        assert_eq!(-12_345, stream.scan_int_grouped(' ').expect("-12 345"));
+        assert_eq!(7, stream.scan_int_grouped(' ').expect("7"));
+    }
+
+    #[test]
+    fn test_scan_float_fmt() {
+        let text = "1.234,56 -0,5 1234,5 12.34,5 1,2.3 1.5 7";
+        let mut stream = InputStream::new(text.as_bytes());
+        let german = |stream: &mut InputStream<&[u8]>| stream.scan_float_fmt(',', Some('.'));
+        assert_eq!(1234.56, german(&mut stream).expect("1.234,56"));
+        assert_eq!(-0.5, german(&mut stream).expect("-0,5"));
+        assert_eq!(1234.5, german(&mut stream).expect("1234,5"));
+        for _ in 0..2 {
+            assert!(matches!(german(&mut stream), Err(Error::Unexpected { .. })));
+        }
+        assert!(matches!(
+            stream.scan_float_fmt(',', None),
+            Err(Error::Unexpected { .. })
+        ));
+        assert_eq!(7.0, stream.scan_float_fmt(',', None).expect("7"));
+
+        let mut stream = InputStream::new("1,234,567.25".as_bytes());
+        assert_eq!(
+            1_234_567.25,
+            stream.scan_float_fmt('.', Some(',')).expect("1,234,567.25")
+        );
+
+        let mut stream = InputStream::new("1,5".as_bytes());
+        match stream.scan_float_fmt(',', Some(',')) {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::InvalidInput => {}
+            other => panic!(
+                "expected the same characters to be rejected, got {:?}",
+                other
+            ),
+        }
+        assert_eq!(1.5, stream.scan_float_fmt(',', None).expect("1,5"));
+    }
+
+    #[test]
+    fn test_enumerated_lines() {
+        let text = "one\r\n\r\n\nfour\r\nfive";
+        let mut stream = InputStream::new(text.as_bytes());
+        let lines: Vec<(usize, String)> = stream
+            .enumerated_lines()
+            .map(|(number, line)| (number, line.expect("a line")))
+            .collect();
+        let expected = vec![(1, "one"), (2, ""), (3, ""), (4, "four"), (5, "five")];
+        let expected: Vec<(usize, String)> = expected
+            .into_iter()
+            .map(|(number, line)| (number, line.to_string()))
+            .collect();
+        assert_eq!(expected, lines);
+    }
+
+    #[test]
+    fn test_scan_field_or() {
+        let text = "1,,3\r\n4\n";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(1, stream.scan_field_or(b',', 0).expect("1"));
+        assert_eq!(0, stream.scan_field_or(b',', 0).expect("empty field"));
+        assert_eq!(3, stream.scan_field_or(b',', 0).expect("3"));
+        assert_eq!(-1, stream.scan_field_or(b',', -1).expect("omitted field"));
+        stream.skip_line().expect("end of the first line");
+        assert_eq!(4, stream.scan_field_or(b',', 0).expect("4"));
+        assert_eq!(0, stream.scan_field_or(b',', 0).expect("omitted field"));
+        stream.skip_line().expect("end of the second line");
+        assert!(stream.scan_field_or(b',', 0).is_err());
+    }
+
+    #[test]
+    fn test_skip_header() {
+        let text = "id,name\r\n1,ana\n";
+        let mut stream = InputStream::new(text.as_bytes());
+        stream.skip_header().expect("header");
+        assert_eq!(1, stream.scan_field_or(b',', 0).expect("1"));
+
+        let mut stream = InputStream::new("".as_bytes());
+        stream.skip_header().expect("no header");
+        assert_eq!(0, stream.bytes_consumed());
+    }
+
+    #[test]
+    fn test_sniff_delimiter() {
+        for &(text, expected) in &[
+            ("a,b,c\n1;2;3;4\n", Some(b',')),
+            ("a;b;\"c,d\"\n", Some(b';')),
+            ("id\tfull name\tage\r\n", Some(b'\t')),
+            ("1 2 3", Some(b' ')),
+            ("a,b;c\n", Some(b',')),
+            ("\na,b", None),
+            ("abc", None),
+            ("", None),
+        ] {
+            let mut stream = InputStream::new(io::BufReader::with_capacity(2, text.as_bytes()));
+            assert_eq!(expected, stream.sniff_delimiter().expect("no io error"));
+            assert_eq!(0, stream.bytes_consumed());
+            assert_eq!(text, stream.scan_remaining().expect("the whole input"));
+        }
+    }
+
+    #[test]
+    fn test_scan_complex() {
+        let text = "3+4i -2-5i 7i 3 -i i 2-i 1e-3+2e+1i -1.5e2 3+4 4i+3";
+        let mut stream = InputStream::new(text.as_bytes());
+        let expected = [
+            (3.0, 4.0),
+            (-2.0, -5.0),
+            (0.0, 7.0),
+            (3.0, 0.0),
+            (0.0, -1.0),
+            (0.0, 1.0),
+            (2.0, -1.0),
+            (1e-3, 20.0),
+            (-150.0, 0.0),
+        ];
+        for &value in expected.iter() {
+            assert_eq!(value, stream.scan_complex().expect("a complex number"));
+        }
+        assert!(matches!(stream.scan_complex(), Err(Error::FromStr(_))));
+        assert!(matches!(stream.scan_complex(), Err(Error::FromStr(_))));
+        assert!(stream.scan_complex().is_err());
+    }
+
+    #[test]
+    fn test_scan_radix_int() {
+        let text = "-0x80 0x80 -0x81 0b101 -0o17 +0XFF 42 -0x1 0x-5";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(-128, stream.scan_radix_int::<i8>().expect("-0x80"));
+        for _ in 0..2 {
+            assert!(matches!(
+                stream.scan_radix_int::<i8>(),
+                Err(Error::FromStr(_))
+            ));
+        }
+        assert_eq!(5, stream.scan_radix_int::<u32>().expect("0b101"));
+        assert_eq!(-15, stream.scan_radix_int::<i64>().expect("-0o17"));
+        assert_eq!(255, stream.scan_radix_int::<u8>().expect("+0XFF"));
+        assert_eq!(42, stream.scan_radix_int::<u16>().expect("42"));
+        assert!(matches!(
+            stream.scan_radix_int::<u8>(),
+            Err(Error::FromStr(_))
+        ));
+        assert!(matches!(
+            stream.scan_radix_int::<i32>(),
+            Err(Error::Unexpected { .. })
+        ));
+    }
+
+    #[test]
+    fn test_scan_version() {
+        let text = "1.2.3 0.10 4 1.2.3.4 1.x.3 1..2 99999999999999999999.0";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!((1, 2, 3), stream.scan_version().expect("1.2.3"));
+        assert_eq!((0, 10, 0), stream.scan_version().expect("0.10"));
+        assert_eq!((4, 0, 0), stream.scan_version().expect("4"));
+        for _ in 0..3 {
+            assert!(matches!(
+                stream.scan_version(),
+                Err(Error::Unexpected { .. })
+            ));
+        }
+        assert!(matches!(stream.scan_version(), Err(Error::FromStr(_))));
+        assert!(stream.scan_version().is_err());
+    }
+
+    #[test]
+    fn test_scan_color() {
+        let text = "#1a2b3c FFFFFF #abc #12345 #12345g";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!((0x1a, 0x2b, 0x3c), stream.scan_color().expect("#1a2b3c"));
+        assert_eq!((255, 255, 255), stream.scan_color().expect("FFFFFF"));
+        assert_eq!((0xaa, 0xbb, 0xcc), stream.scan_color().expect("#abc"));
+        assert!(matches!(stream.scan_color(), Err(Error::Unexpected { .. })));
+        assert!(matches!(stream.scan_color(), Err(Error::Unexpected { .. })));
+    }
+
+    #[test]
+    fn test_scan_enum() {
+        #[derive(Debug, PartialEq)]
+        enum Command {
+            Push,
+            Pop,
+        }
+
+        impl FromStr for Command {
+            type Err = ();
+
+            fn from_str(s: &str) -> std::result::Result<Self, ()> {
+                match s {
+                    "push" => Ok(Command::Push),
+                    "pop" => Ok(Command::Pop),
+                    _ => Err(()),
+                }
+            }
+        }
+
+        let text = "push pop peek";
+        let mut stream = InputStream::new(text.as_bytes());
+        let allowed = ["push", "pop"];
+        assert_eq!(Command::Push, stream.scan_enum(&allowed).expect("push"));
+        assert_eq!(Command::Pop, stream.scan_enum(&allowed).expect("pop"));
+        match stream.scan_enum::<Command>(&allowed) {
+            Err(err @ Error::Unexpected { .. }) => {
+                assert_eq!(
+                    "Expected one of `push`, `pop`, found `peek`",
+                    err.to_string()
+                )
+            }
+            _ => panic!("expected peek to be rejected"),
+        }
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn test_scan_smallvec() {
+        let mut stream = InputStream::new("1 2 3\n4 5 6 7 8".as_bytes());
+        let triangle = stream.scan_smallvec::<u32, 3>(3).expect("a triangle");
+        assert_eq!(&[1, 2, 3], &triangle[..]);
+        assert!(!triangle.spilled());
+        let polygon = stream.scan_smallvec::<u32, 3>(5).expect("a polygon");
+        assert_eq!(&[4, 5, 6, 7, 8], &polygon[..]);
+        assert!(polygon.spilled());
+        match stream.scan_smallvec::<u32, 3>(1) {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("expected an unexpected end of input, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_scan_regex() {
+        let identifier = regex::Regex::new("[a-z_][a-z0-9_]*").expect("a regex");
+        let text = "snake_case 1st abc1 abc-";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!("snake_case", stream.scan_regex(&identifier).expect("ident"));
+        assert!(matches!(
+            stream.scan_regex(&identifier),
+            Err(Error::Unexpected { .. })
+        ));
+        assert_eq!("abc1", stream.scan_regex(&identifier).expect("abc1"));
+        match stream.scan_regex(&identifier) {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("abc-", found),
+            _ => panic!("expected a partial match to be rejected"),
+        }
+
+        let alternatives = regex::Regex::new("a|ab").expect("a regex");
+        let longest_first = regex::Regex::new("ab|a").expect("a regex");
+        let any = regex::Regex::new("a*").expect("a regex");
+        let mut stream = InputStream::new("ab ab a".as_bytes());
+        assert!(matches!(
+            stream.scan_regex(&alternatives),
+            Err(Error::Unexpected { .. })
+        ));
+        assert_eq!("ab", stream.scan_regex(&longest_first).expect("ab"));
+        assert_eq!("a", stream.scan_regex(&any).expect("a"));
+        match stream.scan_regex(&any) {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("expected an unexpected end of input, got {:?}", other),
+        }
+
+        let hex = regex::RegexBuilder::new("[0-9a-f]+")
+            .case_insensitive(true)
+            .build()
+            .expect("a regex");
+        let mut stream = InputStream::new("Ff0A xyz".as_bytes());
+        assert_eq!("Ff0A", stream.scan_regex(&hex).expect("Ff0A"));
+        assert!(stream.scan_regex(&hex).is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_scan_until_regex() {
+        let separator = regex::Regex::new(r"\s*[,;]\s*").expect("a regex");
+        let text = "alpha ,  beta;gamma ţ";
+        for capacity in 1..text.len() + 1 {
+            let mut stream =
+                InputStream::new(io::BufReader::with_capacity(capacity, text.as_bytes()));
+            assert_eq!("alpha", stream.scan_until_regex(&separator).expect("alpha"));
+            assert_eq!("", stream.scan_until_regex(&separator).expect("separator"));
+            assert_eq!(",", stream.scan::<String>().expect(","));
+            assert_eq!("  beta", stream.scan_until_regex(&separator).expect("beta"));
+            assert_eq!(';', stream.scan_char().expect(";"));
+            assert_eq!(
+                "gamma ţ",
+                stream.scan_until_regex(&separator).expect("gamma ţ")
+            );
+            assert_eq!(text.len() as u64, stream.bytes_consumed());
+        }
+    }
+
+    #[test]
+    fn test_scan_digits() {
+        let text = "123456789012345678901234567890 12x4 7";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(4, text.as_bytes()));
+        let mut sum = 0;
+        assert_eq!(
+            30,
+            stream
+                .scan_digits(|d| sum += u32::from(d - b'0'))
+                .expect("digits")
+        );
+        assert_eq!(135, sum);
+        assert!(matches!(
+            stream.scan_digits(|_| ()),
+            Err(Error::Unexpected { .. })
+        ));
+        assert_eq!(1, stream.scan_digits(|d| assert_eq!(b'7', d)).expect("7"));
+        assert_eq!(0, stream.scan_digits(|_| ()).expect("nothing left"));
+    }
+
+    #[test]
+    fn test_rewind() {
+        let text = "1 2 3";
+        let mut stream = InputStream::new(io::Cursor::new(text.as_bytes()));
+        assert_eq!(1, stream.scan().expect("1"));
+        assert!(stream.next_is::<i32>().expect("peek 2"));
+        stream.rewind().expect("rewind");
+        assert_eq!(0, stream.bytes_consumed());
+        assert_eq!(vec![1, 2, 3], stream.scan_all::<i32>().expect("1 2 3"));
+    }
+
+    #[test]
+    fn test_rewind_drops_pushed_back_char() {
+        let mut stream = InputStream::new(io::Cursor::new("ab".as_bytes()));
+        assert_eq!('a', stream.scan_char().expect("a"));
+        stream.push_back_char('z');
+        stream.rewind().expect("rewind");
+        assert_eq!('a', stream.scan_char().expect("a"));
+        assert_eq!('b', stream.scan_char().expect("b"));
+    }
+
+    #[test]
+    fn test_rewind_resets_empty_fields() {
+        let mut stream = InputStream::with_delimiter_set(io::Cursor::new(",x".as_bytes()), b",");
+        stream.keep_empty_fields(true);
+        assert_eq!("", stream.scan::<String>().expect("empty"));
+        stream.rewind().expect("rewind");
+        assert_eq!("", stream.scan::<String>().expect("empty"));
+        assert_eq!("x", stream.scan::<String>().expect("x"));
+    }
+
+    #[test]
+    fn test_scan_optional() {
+        let text = "4 NA -2 na";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(Some(4), stream.scan_optional::<i32>("NA").expect("4"));
+        assert_eq!(None, stream.scan_optional::<i32>("NA").expect("NA"));
+        assert_eq!(Some(-2), stream.scan_optional::<i32>("NA").expect("-2"));
+        assert!(matches!(
+            stream.scan_optional::<i32>("NA"),
+            Err(Error::FromStr(_))
+        ));
+    }
+
+    #[test]
+    fn test_scan_bits() {
+        let text = "0110 1 0120";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![false, true, true, false],
+            stream.scan_bits().expect("0110")
+        );
+        assert_eq!(vec![true], stream.scan_bits().expect("1"));
+        assert!(matches!(stream.scan_bits(), Err(Error::Unexpected { .. })));
+    }
+
+    #[test]
+    fn test_literals() {
+        let text = "BEGIN 1 2 END";
+        let mut stream = InputStream::new(text.as_bytes());
+        stream.expect_literal("BEGIN").expect("BEGIN");
+        assert!(!stream.consume_literal("END").expect("not END yet"));
+        assert_eq!(1, stream.scan().expect("1"));
+        assert!(matches!(
+            stream.expect_literal("END"),
+            Err(Error::Unexpected { .. })
+        ));
+        assert!(stream.consume_literal("END").expect("END"));
+        assert!(stream.scan::<String>().expect("nothing").is_empty());
+
+        let mut stream = InputStream::with_delimiter_set("x,1,,2".as_bytes(), b",");
+        stream.keep_empty_fields(true);
+        assert!(stream.consume_literal("x").expect("x"));
+        assert_eq!(1, stream.scan::<i32>().expect("1"));
+        assert!(!stream.consume_literal("x").expect("an empty field"));
+        assert_eq!("", stream.scan::<String>().expect("empty"));
+        assert_eq!(2, stream.scan::<i32>().expect("2"));
+        assert!(!stream.consume_literal("").expect("the end"));
+        assert!(stream.scan_all::<String>().expect("nothing").is_empty());
+
+        let mut stream = InputStream::new("a ".as_bytes());
+        assert!(!stream.consume_literal("").expect("not a token"));
+        assert!(stream.consume_literal("a").expect("a"));
+        assert!(!stream.consume_literal("").expect("the end"));
+    }
+
+    #[test]
+    fn test_consume_prefix() {
+        let text = "<<data>> <<";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(1, text.as_bytes()));
+        assert!(!stream.consume_prefix("<<dat!").expect("mismatch"));
+        assert_eq!(0, stream.bytes_consumed());
+        assert!(stream.consume_prefix("<<").expect("<<"));
+        assert!(stream.consume_prefix("").expect("nothing"));
+        assert!(stream.consume_prefix("data").expect("data"));
+        assert_eq!(">>", stream.scan::<String>().expect(">>"));
+        assert!(!stream.consume_prefix("<<").expect("whitespace first"));
+        assert_eq!(' ', stream.chars().next().expect("a char").expect(" "));
+        assert!(!stream.consume_prefix("<<<").expect("end of the input"));
+        assert!(stream.consume_prefix("<<").expect("<<"));
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+    }
+
+    #[test]
+    fn test_scan_with_ctx() {
+        let text = "x y x 7";
+        let mut stream = InputStream::new(text.as_bytes());
+        let mut interner: Vec<String> = Vec::new();
+        let intern = |interner: &mut Vec<String>, token: &str| {
+            if token.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err("identifiers start with a letter".into());
+            }
+            match interner.iter().position(|known| known == token) {
+                Some(index) => Ok(index),
+                None => {
+                    interner.push(token.to_string());
+                    Ok(interner.len() - 1)
+                }
+            }
+        };
+        let ids: Vec<usize> = (0..3)
+            .map(|_| stream.scan_with_ctx(&mut interner, intern).expect("an id"))
+            .collect();
+        assert_eq!(vec![0, 1, 0], ids);
+        assert_eq!(vec!["x", "y"], interner);
+        assert!(stream.scan_with_ctx(&mut interner, intern).is_err());
+    }
+
+    #[test]
+    fn test_delimiter_find() {
+        let sets: [&[u8]; 8] = [
+            b"",
+            b",",
+            b",;",
+            b",;:",
+            b",;:|",
+            b",;:| ",
+            b" \x09\x0a\x0b\x0c\x0d",
+            b" \x09\x0a\x0b\x0c\x0d,",
+        ];
+        let mut text = vec![b'x'; 150];
+        text.extend_from_slice(b"abc\x0d,de;f:g|h i\x09j\x0ak\x0bl\x0cm");
+        for set in sets.iter() {
+            let delimiters = Delimiters::new(set);
+            for start in 0..text.len() {
+                let buf = &text[start..];
+                let expected = buf.iter().position(|&c| set.contains(&c));
+                assert_eq!(expected, delimiters.find(buf));
+            }
+        }
+    }
+
+    #[test]
+    fn test_drain() {
+        let text = "7 the rest is ignored";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert!(stream.next_is::<i32>().expect("peek 7"));
+        assert_eq!(text.len() as u64, stream.drain().expect("drain"));
+        assert_eq!(0, stream.drain().expect("drain at end"));
+    }
+
+    #[test]
+    fn test_scan_all_bytes() {
+        let text = b"P5 2\n\x00\xff \x10";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(3, &text[..]));
+        assert_eq!("P5", stream.scan::<String>().expect("P5"));
+        assert!(stream.next_is::<u32>().expect("peek 2"));
+        assert_eq!(2, stream.scan::<u32>().expect("2"));
+        stream.skip_line().expect("end of the header");
+        assert_eq!(
+            b"\x00\xff \x10".to_vec(),
+            stream.scan_all_bytes().expect("body")
+        );
+        assert!(stream.scan_all_bytes().expect("nothing").is_empty());
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+    }
+
+    #[test]
+    fn test_exact_line() {
+        let text = "1 2 3\r\n4 5\n6 7 8 9\n10 11 12";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            vec![1, 2, 3],
+            stream.scan_exact_line::<i32>(3).expect("1 2 3")
+        );
+        match stream.scan_exact_line::<i32>(3) {
+            Err(Error::WrongCount { expected, found }) => assert_eq!((3, 2), (expected, found)),
+            _ => panic!("expected a count error"),
+        }
+        assert!(stream.scan_exact_line::<i32>(3).is_err());
+        assert_eq!(
+            vec![10, 11, 12],
+            stream.scan_exact_line::<i32>(3).expect("10 11 12")
+        );
+        for n in [0, 3] {
+            match stream.scan_exact_line::<i32>(n) {
+                Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+                other => panic!("expected the end of the input, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_refill_boundaries() {
+        let text = " 12 -3.5  1000000 7\n\n42 x 999999999 0.25";
+        let whole: Vec<String> = InputStream::new(text.as_bytes()).scan_all().expect("whole");
+        for capacity in 1..text.len() + 1 {
+            let mut stream =
+                InputStream::new(io::BufReader::with_capacity(capacity, text.as_bytes()));
+            let tokens: Vec<String> = stream.scan_all().expect("tokens");
+            assert_eq!(whole, tokens);
+
+            let mut stream =
+                InputStream::new(io::BufReader::with_capacity(capacity, text.as_bytes()));
+            assert_eq!(12, stream.scan::<u32>().expect("12"));
+            assert_eq!(-3.5, stream.scan::<f64>().expect("-3.5"));
+            assert_eq!(
+                1_000_000,
+                stream.scan_with_limit::<u32>(7).expect("1000000")
+            );
+            assert_eq!(7, stream.scan::<u8>().expect("7"));
+            assert_eq!(42, stream.scan::<i64>().expect("42"));
+            assert!(stream.scan::<i64>().is_err());
+            assert_eq!(999_999_999, stream.scan::<u64>().expect("999999999"));
+            assert_eq!(0.25, stream.scan::<f32>().expect("0.25"));
+            assert_eq!(text.len() as u64, stream.bytes_consumed());
+        }
+    }
+
+    #[test]
+    fn test_scan_cow() {
+        let text = "  alpha beta\ngamma";
+        let mut stream = InputStream::new(text.as_bytes());
+        match stream.scan_cow().expect("alpha") {
+            Cow::Borrowed(token) => assert_eq!("alpha", token),
+            Cow::Owned(_) => panic!("alpha fits in the window"),
+        }
+        assert_eq!(7, stream.bytes_consumed());
+        assert_eq!("beta", stream.scan_cow().expect("beta"));
+        match stream.scan_cow().expect("gamma") {
+            Cow::Owned(token) => assert_eq!("gamma", token),
+            Cow::Borrowed(_) => panic!("gamma ends at the end of the input"),
+        }
+        assert_eq!("", stream.scan_cow().expect("end"));
+
+        let mut stream = InputStream::new(io::BufReader::with_capacity(4, text.as_bytes()));
+        let mut tokens = Vec::new();
+        loop {
+            let token = stream.scan_cow().expect("token").into_owned();
+            if token.is_empty() {
+                break;
+            }
+            tokens.push(token);
+        }
+        assert_eq!(vec!["alpha", "beta", "gamma"], tokens);
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+    }
+
+    #[test]
+    fn test_scan_str_into() {
+        let text = "a a b c";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(2, text.as_bytes()));
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        {
+            let a = stream.scan_str_into(&mut first).expect("a");
+            let b = stream.scan_str_into(&mut second).expect("a");
+            assert_eq!(a, b);
+        }
+        {
+            let b = stream.scan_str_into(&mut first).expect("b");
+            let c = stream.scan_str_into(&mut second).expect("c");
+            assert_eq!(("b", "c"), (b, c));
+        }
+        assert_eq!("", stream.scan_str_into(&mut first).expect("end"));
+    }
+
+    #[test]
+    fn test_socket_addr() {
+        let text = "127.0.0.1:8080 [::1]:443 10.0.0.1 [fe80::1] 300.0.0.1:80";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(
+            "127.0.0.1:8080".parse::<SocketAddr>().unwrap(),
+            stream.scan_socket_addr().expect("ipv4")
+        );
+        assert_eq!(
+            "[::1]:443".parse::<SocketAddr>().unwrap(),
+            stream.scan_socket_addr().expect("ipv6")
+        );
+        for missing in ["10.0.0.1", "[fe80::1]"].iter() {
+            match stream.scan_socket_addr() {
+                Err(Error::Unexpected { found, .. }) => assert_eq!(missing, &found),
+                _ => panic!("expected a missing port error"),
+            }
+        }
+        match stream.scan_socket_addr() {
+            Err(Error::FromStr(_)) => {}
+            _ => panic!("expected a bad address error"),
+        }
+    }
+
+    #[test]
+    fn test_ranges() {
+        let text = "3..7 -2..-1 0.5..1.5 3..=7 1..=1 5 1..2";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!(3..7, stream.scan_range::<i32>().expect("3..7"));
+        assert_eq!(-2..-1, stream.scan_range::<i32>().expect("-2..-1"));
+        assert_eq!(0.5..1.5, stream.scan_range::<f64>().expect("0.5..1.5"));
+        match stream.scan_range::<i32>() {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("3..=7", found),
+            _ => panic!("3..=7 is not exclusive"),
+        }
+        assert_eq!(1..=1, stream.scan_range_inclusive::<u8>().expect("1..=1"));
+        assert!(stream.scan_range_inclusive::<u8>().is_err());
+        assert!(stream.scan_range_inclusive::<u8>().is_err());
+    }
+
+    #[test]
+    fn test_scan_with_checksum() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let text = "  12\n34 \t56";
+        let mut stream = InputStream::new(io::BufReader::with_capacity(3, text.as_bytes()));
+        let mut hasher = DefaultHasher::new();
+        let mut sum = 0;
+        for _ in 0..3 {
+            sum += stream
+                .scan_with_checksum::<i32, _>(&mut hasher)
+                .expect("value");
+        }
+        assert_eq!(102, sum);
+
+        let mut expected = DefaultHasher::new();
+        expected.write(text.as_bytes());
+        assert_eq!(expected.finish(), hasher.finish());
+
+        let text = "a,,b";
+        let mut stream = InputStream::with_delimiter_set(text.as_bytes(), b",");
+        stream.keep_empty_fields(true);
+        let mut hasher = DefaultHasher::new();
+        let fields: Vec<String> = (0..3)
+            .map(|_| stream.scan_with_checksum(&mut hasher).expect("field"))
+            .collect();
+        assert_eq!(vec!["a", "", "b"], fields);
+
+        let mut expected = DefaultHasher::new();
+        expected.write(text.as_bytes());
+        assert_eq!(expected.finish(), hasher.finish());
+    }
+
+    #[test]
+    fn test_scan_with_eof_flag() {
+        let text = "1 2\n3  \n";
+        let mut stream = InputStream::new(text.as_bytes());
+        let mut values = Vec::new();
+        loop {
+            let (value, last) = stream.scan_with_eof_flag::<i32>().expect("value");
+            values.push(value);
+            if last {
+                break;
+            }
+        }
+        assert_eq!(vec![1, 2, 3], values);
+        assert_eq!(5, stream.bytes_consumed());
+    }
+
+    #[test]
+    fn test_scan_exact() {
+        let mut stream = InputStream::new("  42 \n\n".as_bytes());
+        assert_eq!(42, stream.scan_exact::<i32>().expect("42"));
+
+        let mut stream = InputStream::new("42\n7 8".as_bytes());
+        match stream.scan_exact::<i32>() {
+            Err(Error::Unexpected { found, .. }) => assert_eq!("7", found),
+            _ => panic!("expected trailing data to be rejected"),
+        }
+        assert_eq!(7, stream.scan().expect("7"));
+    }
+
+    #[test]
+    fn test_points() {
+        let text = "1 2\n-3 4.5 0.5 6 7 8 9";
+        let mut stream = InputStream::new(text.as_bytes());
+        assert_eq!((1u8, 2u64), stream.scan_pair().expect("pair"));
+        assert!(stream.scan_point::<i32>().is_err());
+        assert_eq!((0.5, 6.0), stream.scan_point::<f64>().expect("point"));
+        assert_eq!((7, 8, 9), stream.scan_point3::<i32>().expect("point3"));
+        match stream.scan_point::<i32>() {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            _ => panic!("expected an unexpected end of input"),
+        }
+    }
+
+    #[test]
+    fn test_scan_columns() {
+        let text = "1 50\n2 150\n3 x";
+        let mut stream = InputStream::new(text.as_bytes());
+        let (ids, weights) = stream.scan_columns::<u8, u8>(0).expect("no pairs");
+        assert!(ids.is_empty() && weights.is_empty());
+        let (ids, weights) = stream.scan_columns::<u8, u64>(2).expect("two pairs");
+        assert_eq!((vec![1, 2], vec![50, 150]), (ids, weights));
+        assert!(stream.scan_columns::<u8, u64>(1).is_err());
+    }
+
+    #[test]
+    fn test_keep_empty_fields() {
+        let text = ",a,,b,c\n";
+        let mut stream = InputStream::with_delimiter_set(text.as_bytes(), b",\n");
+        stream.keep_empty_fields(true);
+        let fields: Vec<String> = (0..6).map(|_| stream.scan().expect("field")).collect();
+        assert_eq!(vec!["", "a", "", "b", "c", ""], fields);
+
+        let mut stream = InputStream::with_delimiter_set(text.as_bytes(), b",\n");
+        let fields: Vec<String> = stream.scan_all().expect("fields");
+        assert_eq!(vec!["a", "b", "c"], fields);
+
+        let text = "1,,3\n,5,\n";
+        let mut stream = InputStream::with_delimiter_set(text.as_bytes(), b",");
+        stream.keep_empty_fields(true);
+        assert_eq!(1, stream.scan::<i32>().expect("1"));
+        assert_eq!("", stream.scan_cow().expect("empty"));
+        assert_eq!(",3", stream.scan_line().expect("rest of the line"));
+        assert_eq!("", stream.scan::<String>().expect("empty"));
+        assert_eq!(5, stream.scan::<i32>().expect("5"));
+        assert_eq!("\n", stream.scan::<String>().expect("newline"));
+    }
+
+    #[test]
+    fn test_keep_empty_fields_collecting() {
+        let keeping = |text: &'static str| {
+            InputStreamBuilder::new()
+                .delimiters(b",\n")
+                .keep_empty_fields(true)
+                .build(io::BufReader::with_capacity(2, text.as_bytes()))
+        };
+
+        let fields: Vec<String> = keeping("a,,b").scan_all().expect("fields");
+        assert_eq!(vec!["a", "", "b"], fields);
+        let fields: Vec<String> = keeping(",a,,b,c\n").scan_all().expect("fields");
+        assert_eq!(vec!["", "a", "", "b", "c"], fields);
+
+        let mut stream = keeping("a,,b");
+        assert_eq!(
+            vec!["a", "", "b"],
+            stream.scan_n::<String>(3).expect("fields")
+        );
+        match stream.scan_n::<String>(1) {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("expected an unexpected end of input, got {:?}", other),
+        }
+
+        let mut stream = keeping("a,,b\n,");
+        let tokens: Vec<String> = stream
+            .tokens()
+            .map(|token| token.expect("a field"))
+            .collect();
+        assert_eq!(vec!["a", "", "b", ""], tokens);
+    }
+
+    #[test]
+    fn test_scan_to_channel() {
+        use std::sync::mpsc;
+        use std::thread;
+
+        let (tx, rx) = mpsc::channel();
+        let stream = InputStream::new("1 2 3\nx 5".as_bytes());
+        let producer = thread::spawn(move || stream.scan_to_channel::<i32>(tx));
+        let received: Vec<Result<i32>> = rx.iter().collect();
+        producer.join().expect("producer");
+        assert_eq!(4, received.len());
+        let values: Vec<i32> = received[..3]
+            .iter()
+            .map(|value| *value.as_ref().expect("a value"))
+            .collect();
+        assert_eq!(vec![1, 2, 3], values);
+        assert!(matches!(received[3], Err(Error::FromStr(_))));
+    }
+
+    #[test]
+    fn test_builder() {
+        let builder = InputStreamBuilder::new()
+            .delimiters(b";\n")
+            .keep_empty_fields(true)
+            .skip_trailing_delimiter(true)
+            .interrupt_retries(Some(1));
+        let mut stream = builder.build("a;;b;\nc".as_bytes());
+        let fields: Vec<String> = (0..4).map(|_| stream.scan().expect("field")).collect();
+        assert_eq!(vec!["a", "", "b", "c"], fields);
+
+        let mut stream = InputStreamBuilder::default().build("a;b c".as_bytes());
+        assert_eq!("a;b", stream.scan::<String>().expect("a;b"));
+    }
+
+    #[test]
+    fn test_skip_trailing_delimiter() {
+        let text = "1;2;3;\n4;;6\n\n7;";
+        let mut stream = InputStream::with_delimiter_set(text.as_bytes(), b";\n");
+        stream.keep_empty_fields(true);
+        stream.skip_trailing_delimiter(true);
+        assert_eq!(vec![1, 2, 3], stream.scan_n::<i32>(3).expect("1 2 3"));
+        assert_eq!(4, stream.scan::<i32>().expect("4"));
+        let fields: Vec<String> = (0..5).map(|_| stream.scan().expect("field")).collect();
+        assert_eq!(vec!["", "6", "", "7", ""], fields);
+        assert_eq!(text.len() as u64, stream.bytes_consumed());
+
+        let mut stream = InputStream::with_delimiter_set("1;2;3;\n".as_bytes(), b";\n");
+        stream.keep_empty_fields(true);
+        assert_eq!(vec![1, 2, 3], stream.scan_n::<i32>(3).expect("1 2 3"));
+        assert_eq!("", stream.scan::<String>().expect("empty fourth value"));
     }
 }